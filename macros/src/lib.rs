@@ -0,0 +1,355 @@
+//! Procedural macros for [`js-function-promisify`](https://docs.rs/js-function-promisify).
+use proc_macro::TokenStream;
+use proc_macro2::{Group, Ident, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::spanned::Spanned;
+use syn::{
+  Attribute, Data, DeriveInput, Error, Expr, FnArg, ForeignItem, ForeignItemFn, Index, ItemForeignMod, Member, Pat,
+  Path, ReturnType, Type,
+};
+
+/// Generates an `async fn` wrapper for each function marked `#[promisify(..)]` in a `wasm_bindgen` extern
+/// block. The wrapper takes the same arguments, except for the callbacks, which it creates and passes itself,
+/// and returns the awaited `Result<JsValue, JsValue>`.
+///
+/// Each marker names the callback parameters, and the kind of callback to create:
+/// - `callback = cb`, with `Callback::default()`, resolving with the first argument it is called with.
+/// - `node = cb`, with `Callback::default_node()`, for an `(err, data)` callback.
+/// - `resolve = ok, reject = err`, with `CallbackPair::default()`.
+///
+/// The wrapper is named after the function with an `_async` suffix, unless given with `rename = name`. If the
+/// function is `catch`, an exception is returned as Err immediately. This attribute must come before
+/// `#[wasm_bindgen]`, so the markers are removed before it sees them.
+///
+/// ```ignore
+/// #[promisify]
+/// #[wasm_bindgen]
+/// extern "C" {
+///   #[promisify(node = cb)]
+///   fn read_file(path: &str, cb: &Function);
+/// }
+///
+/// let contents = read_file_async("data.txt").await?;
+/// ```
+#[proc_macro_attribute]
+pub fn promisify(attr: TokenStream, item: TokenStream) -> TokenStream {
+  expand(attr.into(), item.into())
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+fn expand(attr: TokenStream2, item: TokenStream2) -> syn::Result<TokenStream2> {
+  if !attr.is_empty() {
+    return Err(Error::new(attr.span(), "#[promisify] on an extern block takes no arguments"));
+  }
+  let mut block: ItemForeignMod = syn::parse2(item)?;
+  let mut wrappers = Vec::new();
+  for item in block.items.iter_mut() {
+    if let ForeignItem::Fn(func) = item {
+      if let Some(marker) = take_marker(&mut func.attrs)? {
+        wrappers.push(wrapper(func, marker)?);
+      }
+    }
+  }
+  Ok(quote! {
+    #block
+    #(#wrappers)*
+  })
+}
+
+/// The callbacks a marked function takes, which the wrapper creates and passes itself.
+enum Callbacks {
+  Callback(Ident),
+  Node(Ident),
+  Pair(Ident, Ident),
+}
+
+struct Marker {
+  callbacks: Callbacks,
+  rename: Option<Ident>,
+}
+
+/// Removes the `#[promisify(..)]` marker from a function's attributes, and parses it.
+fn take_marker(attrs: &mut Vec<Attribute>) -> syn::Result<Option<Marker>> {
+  let index = match attrs.iter().position(|attr| attr.path().is_ident("promisify")) {
+    Some(index) => index,
+    None => return Ok(None),
+  };
+  let attr = attrs.remove(index);
+  let (mut callback, mut node, mut resolve, mut reject, mut rename) = (None, None, None, None, None);
+  attr.parse_nested_meta(|meta| {
+    let slot = match meta.path.get_ident().map(Ident::to_string).as_deref() {
+      Some("callback") => &mut callback,
+      Some("node") => &mut node,
+      Some("resolve") => &mut resolve,
+      Some("reject") => &mut reject,
+      Some("rename") => &mut rename,
+      _ => return Err(meta.error("expected `callback`, `node`, `resolve`, `reject` or `rename`")),
+    };
+    *slot = Some(meta.value()?.parse::<Ident>()?);
+    Ok(())
+  })?;
+  let callbacks = match (callback, node, resolve, reject) {
+    (Some(cb), None, None, None) => Callbacks::Callback(cb),
+    (None, Some(cb), None, None) => Callbacks::Node(cb),
+    (None, None, Some(ok), Some(err)) => Callbacks::Pair(ok, err),
+    _ => {
+      let msg = "expected one of `callback = ..`, `node = ..`, or both `resolve = ..` and `reject = ..`";
+      return Err(Error::new(attr.span(), msg));
+    }
+  };
+  Ok(Some(Marker { callbacks, rename }))
+}
+
+/// How a function declared in an extern block is called from rust.
+enum Receiver {
+  Free,
+  Method,
+  Static(Path),
+}
+
+/// Reads whether a function is a method or static method from its `#[wasm_bindgen(..)]` attributes, and whether
+/// it catches exceptions.
+fn receiver(attrs: &[Attribute]) -> syn::Result<(Receiver, bool)> {
+  let (mut receiver, mut catch) = (Receiver::Free, false);
+  for attr in attrs.iter().filter(|attr| attr.path().is_ident("wasm_bindgen")) {
+    attr.parse_nested_meta(|meta| {
+      if meta.path.is_ident("method") {
+        receiver = Receiver::Method;
+      } else if meta.path.is_ident("static_method_of") {
+        receiver = Receiver::Static(meta.value()?.parse()?);
+      } else if meta.path.is_ident("catch") {
+        catch = true;
+      } else if meta.input.peek(syn::Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+      } else if meta.input.peek(syn::token::Paren) {
+        meta.input.parse::<Group>()?;
+      }
+      Ok(())
+    })?;
+  }
+  Ok((receiver, catch))
+}
+
+fn wrapper(func: &ForeignItemFn, marker: Marker) -> syn::Result<TokenStream2> {
+  let sig = &func.sig;
+  let (receiver, catch) = receiver(&func.attrs)?;
+  let name = &sig.ident;
+  let rename = marker.rename.unwrap_or_else(|| format_ident!("{}_async", name));
+  let callbacks: Vec<&Ident> = match &marker.callbacks {
+    Callbacks::Callback(cb) | Callbacks::Node(cb) => vec![cb],
+    Callbacks::Pair(ok, err) => vec![ok, err],
+  };
+
+  let (mut params, mut args, mut found, mut this) = (Vec::new(), Vec::new(), Vec::new(), None);
+  for input in sig.inputs.iter() {
+    let input = match input {
+      FnArg::Typed(input) => input,
+      FnArg::Receiver(input) => return Err(Error::new(input.span(), "expected a typed argument")),
+    };
+    let ident = match &*input.pat {
+      Pat::Ident(pat) => &pat.ident,
+      pat => return Err(Error::new(pat.span(), "expected an identifier")),
+    };
+    if this.is_none() {
+      this = Some(&input.ty);
+    }
+    if callbacks.contains(&ident) {
+      found.push(ident);
+      args.push(quote!(&#ident));
+    } else {
+      params.push(input);
+      args.push(quote!(#ident));
+    }
+  }
+  for cb in callbacks.iter() {
+    if !found.contains(cb) {
+      return Err(Error::new(cb.span(), format!("`{}` is not an argument of `{}`", cb, name)));
+    }
+  }
+
+  let path = match receiver {
+    Receiver::Free => quote!(#name),
+    Receiver::Static(ty) => quote!(#ty::#name),
+    Receiver::Method => match this.map(|ty| &**ty) {
+      Some(Type::Reference(ty)) => {
+        let ty = &ty.elem;
+        quote!(<#ty>::#name)
+      }
+      _ => return Err(Error::new(sig.span(), "expected a method to take `this` by reference")),
+    },
+  };
+  let call = match (&sig.output, catch) {
+    (ReturnType::Type(..), true) => quote!(#path(#(#args),*)?;),
+    _ => quote!(let _ = #path(#(#args),*);),
+  };
+  // Resolved at the macro's own definition site, so it never shadows an argument of the same name.
+  let future = Ident::new("future", Span::mixed_site());
+  let create = match &marker.callbacks {
+    Callbacks::Callback(cb) => quote! {
+      let #future = ::js_function_promisify::Callback::default();
+      let #cb = #future.as_function();
+    },
+    Callbacks::Node(cb) => quote! {
+      let #future = ::js_function_promisify::Callback::default_node();
+      let #cb = #future.as_function();
+    },
+    Callbacks::Pair(ok, err) => quote! {
+      let #future = ::js_function_promisify::CallbackPair::default();
+      let (#ok, #err) = #future.as_functions();
+    },
+  };
+  let vis = &func.vis;
+  let doc = format!("Calls [`{}`], and waits for its callback.", name);
+  Ok(quote! {
+    #[doc = #doc]
+    #vis async fn #rename(#(#params),*) -> ::core::result::Result<::wasm_bindgen::JsValue, ::wasm_bindgen::JsValue> {
+      #create
+      #call
+      #future.await
+    }
+  })
+}
+
+/// Implements `FromCallbackArgs` for a struct, mapping each field to the callback argument at the same position,
+/// so it can be created with `Callback::typed`. Each field is converted with `FromArg`, or with `JsCast` if marked
+/// `#[callback(cast)]`.
+///
+/// ```ignore
+/// #[derive(FromCallbackArgs)]
+/// struct Read {
+///   err: Option<JsValue>,
+///   bytes_read: u32,
+///   #[callback(cast)]
+///   buffer: Uint8Array,
+/// }
+///
+/// let future = Callback::typed::<Read>();
+/// ```
+#[proc_macro_derive(FromCallbackArgs, attributes(callback))]
+pub fn derive_from_callback_args(item: TokenStream) -> TokenStream {
+  syn::parse2(item.into())
+    .and_then(expand_from_callback_args)
+    .unwrap_or_else(Error::into_compile_error)
+    .into()
+}
+
+fn expand_from_callback_args(input: DeriveInput) -> syn::Result<TokenStream2> {
+  let fields = match &input.data {
+    Data::Struct(data) => &data.fields,
+    _ => return Err(Error::new(input.ident.span(), "FromCallbackArgs can only be derived for a struct")),
+  };
+  if fields.len() > 7 {
+    return Err(Error::new(input.ident.span(), "a callback may take at most 7 arguments"));
+  }
+  let mut values = Vec::new();
+  for (index, field) in fields.iter().enumerate() {
+    let mut cast = false;
+    for attr in field.attrs.iter().filter(|attr| attr.path().is_ident("callback")) {
+      attr.parse_nested_meta(|meta| {
+        if !meta.path.is_ident("cast") {
+          return Err(meta.error("expected `cast`"));
+        }
+        cast = true;
+        Ok(())
+      })?;
+    }
+    let member = match &field.ident {
+      Some(ident) => Member::Named(ident.clone()),
+      None => Member::Unnamed(Index::from(index)),
+    };
+    let ty = &field.ty;
+    let convert = if cast {
+      quote!(::js_function_promisify::cast_arg::<#ty>)
+    } else {
+      quote!(<#ty as ::js_function_promisify::FromArg>::from_arg)
+    };
+    values.push(quote! {
+      #member: #convert(args.next().unwrap_or(::wasm_bindgen::JsValue::UNDEFINED))?
+    });
+  }
+  let name = &input.ident;
+  let count = fields.len();
+  let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+  Ok(quote! {
+    impl #impl_generics ::js_function_promisify::FromCallbackArgs for #name #ty_generics #where_clause {
+      type Args = ::js_function_promisify::Args<#count>;
+
+      fn from_args(
+        args: ::std::vec::Vec<::wasm_bindgen::JsValue>,
+      ) -> ::core::result::Result<Self, ::wasm_bindgen::JsValue> {
+        let mut args = args.into_iter();
+        ::core::result::Result::Ok(#name { #(#values),* })
+      }
+    }
+  })
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{expand, expand_from_callback_args};
+  use quote::quote;
+
+  #[test]
+  fn generates_wrapper() {
+    let item = quote! {
+      #[wasm_bindgen]
+      extern "C" {
+        #[promisify(node = cb)]
+        fn read_file(path: &str, cb: &Function);
+      }
+    };
+    let output = expand(quote!(), item).unwrap().to_string();
+    assert!(output.contains("async fn read_file_async (path : & str)"));
+    assert!(output.contains("read_file (path , & cb)"));
+    assert!(!output.contains("# [promisify")); // Assert the marker was removed
+  }
+
+  #[test]
+  fn calls_methods_through_their_type() {
+    let item = quote! {
+      extern "C" {
+        #[wasm_bindgen(method, catch)]
+        #[promisify(resolve = ok, reject = err, rename = load)]
+        fn load(this: &Loader, ok: &Function, err: &Function) -> Result<(), JsValue>;
+      }
+    };
+    let output = expand(quote!(), item).unwrap().to_string();
+    assert!(output.contains("async fn load (this : & Loader)"));
+    assert!(output.contains("< Loader > :: load (this , & ok , & err) ?"));
+  }
+
+  #[test]
+  fn rejects_unknown_callback() {
+    let item = quote! {
+      extern "C" {
+        #[promisify(callback = missing)]
+        fn after(ms: u32, cb: &Function);
+      }
+    };
+    let err = expand(quote!(), item).unwrap_err();
+    assert_eq!(err.to_string(), "`missing` is not an argument of `after`");
+  }
+
+  #[test]
+  fn derives_by_position() {
+    let input = syn::parse2(quote! {
+      struct Read {
+        err: Option<JsValue>,
+        #[callback(cast)]
+        buffer: Uint8Array,
+      }
+    });
+    let output = expand_from_callback_args(input.unwrap()).unwrap().to_string();
+    assert!(output.contains("Args < 2usize >"));
+    assert!(output.contains("err : < Option < JsValue > as :: js_function_promisify :: FromArg > :: from_arg"));
+    assert!(output.contains("buffer : :: js_function_promisify :: cast_arg :: < Uint8Array >"));
+  }
+
+  #[test]
+  fn rejects_enums() {
+    let input = syn::parse2(quote!(enum Args { A, B }));
+    let err = expand_from_callback_args(input.unwrap()).unwrap_err();
+    assert_eq!(err.to_string(), "FromCallbackArgs can only be derived for a struct");
+  }
+}
@@ -0,0 +1,373 @@
+use core::cell::{Cell, RefCell};
+use std::fmt;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use wasm_bindgen::prelude::Closure;
+use wasm_bindgen::JsValue;
+
+/// The arity-erased body of a callback. It receives every argument the function was called with.
+pub type Handler = Box<dyn FnOnce(Vec<JsValue>)>;
+
+/// Like [`Handler`], for a callback which may be called any number of times.
+pub type HandlerMut = Box<dyn FnMut(Vec<JsValue>)>;
+
+/// Like [`HandlerMut`], for a function which returns a value to its javascript caller.
+pub type HandlerReturn = Box<dyn FnMut(Vec<JsValue>) -> JsValue>;
+
+/// A `Backend` decides what a `Callback` or `CallbackPair` hands out to be called, and how.
+///
+/// The default, [`WasmBackend`], creates a `wasm_bindgen::prelude::Closure` which can only exist on wasm. The
+/// [`FakeBackend`] creates a [`FakeFunction`] instead, so the polling and waking of callback-driven code can be
+/// unit tested with a plain `cargo test`.
+pub trait Backend: 'static + Sized {
+  /// The callable handle for a callback with the signature `F`.
+  type Function<F: 'static + ?Sized>: Debug;
+
+  /// Creates a handle which may be called at most once, after which it runs `handler`.
+  fn once<F: 'static + ?Sized + Signature>(handler: Handler) -> Self::Function<F>;
+
+  /// Creates a handle which runs `handler` every time it is called.
+  fn many<F: 'static + ?Sized + Signature>(handler: HandlerMut) -> Self::Function<F>;
+
+  /// Creates a handle which runs `handler` every time it is called, returning what it returns.
+  fn returning<F: 'static + ?Sized + ReturnSignature>(handler: HandlerReturn) -> Self::Function<F>;
+}
+
+/// The default backend, which creates real javascript functions.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct WasmBackend;
+
+impl Backend for WasmBackend {
+  type Function<F: 'static + ?Sized> = Closure<F>;
+
+  fn once<F: 'static + ?Sized + Signature>(handler: Handler) -> Closure<F> {
+    F::once(handler)
+  }
+
+  fn many<F: 'static + ?Sized + Signature>(handler: HandlerMut) -> Closure<F> {
+    F::many(handler)
+  }
+
+  fn returning<F: 'static + ?Sized + ReturnSignature>(handler: HandlerReturn) -> Closure<F> {
+    F::returning(handler)
+  }
+}
+
+/// A pure-rust backend for tests, whose functions are called with [`FakeFunction::call`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FakeBackend;
+
+impl Backend for FakeBackend {
+  type Function<F: 'static + ?Sized> = FakeFunction<F>;
+
+  fn once<F: 'static + ?Sized + Signature>(handler: Handler) -> FakeFunction<F> {
+    let mut handler = Some(handler);
+    FakeFunction::new(
+      true,
+      Box::new(move |args| {
+        handler.take().unwrap()(args);
+        JsValue::UNDEFINED
+      }),
+    )
+  }
+
+  fn many<F: 'static + ?Sized + Signature>(mut handler: HandlerMut) -> FakeFunction<F> {
+    FakeFunction::new(
+      false,
+      Box::new(move |args| {
+        handler(args);
+        JsValue::UNDEFINED
+      }),
+    )
+  }
+
+  fn returning<F: 'static + ?Sized + ReturnSignature>(handler: HandlerReturn) -> FakeFunction<F> {
+    FakeFunction::new(false, handler)
+  }
+}
+
+/// The stand-in for a javascript function created by the [`FakeBackend`].
+pub struct FakeFunction<F: 'static + ?Sized> {
+  handler: RefCell<Option<HandlerReturn>>,
+  once: bool,
+  calls: Cell<usize>,
+  _marker: PhantomData<Box<F>>,
+}
+
+impl<F: 'static + ?Sized> FakeFunction<F> {
+  fn new(once: bool, handler: HandlerReturn) -> FakeFunction<F> {
+    FakeFunction {
+      handler: RefCell::new(Some(handler)),
+      once,
+      calls: Cell::new(0),
+      _marker: PhantomData,
+    }
+  }
+
+  /// Calls the function the way javascript would, returning what it returns. Missing arguments are `undefined`,
+  /// and extra arguments are ignored. Panics if a one-shot function was already called, or if called
+  /// recursively, where javascript would have thrown.
+  pub fn call(&self, args: Vec<JsValue>) -> JsValue {
+    let handler = match self.handler.borrow_mut().take() {
+      Some(_) if self.once && self.is_called() => None,
+      handler => handler,
+    };
+    let mut handler = handler.expect("closure invoked recursively or after being dropped");
+    self.calls.set(self.calls.get() + 1);
+    let returned = handler(args);
+    if !self.once {
+      self.handler.replace(Some(handler));
+    }
+    returned
+  }
+
+  /// Returns true once the function has been called.
+  pub fn is_called(&self) -> bool {
+    self.calls() > 0
+  }
+
+  /// Returns the number of times the function has been called.
+  pub fn calls(&self) -> usize {
+    self.calls.get()
+  }
+}
+
+impl<F: 'static + ?Sized> Debug for FakeFunction<F> {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.debug_struct("FakeFunction")
+      .field("once", &self.once)
+      .field("calls", &self.calls())
+      .finish()
+  }
+}
+
+/// Implemented for every `dyn FnMut(JsValue, ..)` a callback may be created with, up to 7 args.
+pub trait Signature {
+  /// Creates a javascript closure of this signature which may be called at most once.
+  fn once(handler: Handler) -> Closure<Self>;
+
+  /// Creates a javascript closure of this signature which may be called any number of times.
+  fn many(handler: HandlerMut) -> Closure<Self>;
+}
+
+/// Implemented for every `dyn FnMut(JsValue, ..) -> JsValue` a function returning a value may be created with, up
+/// to 7 args.
+pub trait ReturnSignature {
+  /// Creates a javascript closure of this signature which may be called any number of times.
+  fn returning(handler: HandlerReturn) -> Closure<Self>;
+}
+
+/// A utility macro for generating an implementation of `Signature` and `ReturnSignature` for every argument list.
+macro_rules! signature_impl {
+  ($($alist:ident)*) => {
+    impl Signature for dyn FnMut($(signature_impl!(@rep $alist JsValue)),*) {
+      fn once(handler: Handler) -> Closure<Self> {
+        Closure::once(move |$($alist),*| handler(vec![$($alist),*]))
+      }
+
+      fn many(mut handler: HandlerMut) -> Closure<Self> {
+        Closure::wrap(Box::new(move |$($alist),*| handler(vec![$($alist),*])) as Box<Self>)
+      }
+    }
+
+    impl ReturnSignature for dyn FnMut($(signature_impl!(@rep $alist JsValue)),*) -> JsValue {
+      fn returning(mut handler: HandlerReturn) -> Closure<Self> {
+        Closure::wrap(Box::new(move |$($alist),*| handler(vec![$($alist),*])) as Box<Self>)
+      }
+    }
+  };
+  // Utility for replacing anything with a type.
+  (@rep $_t:tt $sub:ty) => {
+    $sub
+  };
+}
+
+signature_impl!();
+signature_impl!(a0);
+signature_impl!(a0 a1);
+signature_impl!(a0 a1 a2);
+signature_impl!(a0 a1 a2 a3);
+signature_impl!(a0 a1 a2 a3 a4);
+signature_impl!(a0 a1 a2 a3 a4 a5);
+signature_impl!(a0 a1 a2 a3 a4 a5 a6);
+
+/// Pads or truncates the arguments a function was called with to exactly `N`, the way javascript would.
+pub(crate) fn args<const N: usize>(args: Vec<JsValue>) -> [JsValue; N] {
+  let mut args = args.into_iter();
+  [(); N].map(|_| args.next().unwrap_or(JsValue::UNDEFINED))
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Args, Callback, CallbackPair, CallbackStream, ErrorConvention, FakeBackend};
+  use futures_core::Stream;
+  use std::cell::Cell;
+  use std::future::Future;
+  use std::pin::Pin;
+  use std::rc::Rc;
+  use std::sync::atomic::{AtomicUsize, Ordering};
+  use std::sync::Arc;
+  use std::task::{Context, Poll, Wake, Waker};
+  use wasm_bindgen::JsValue;
+
+  #[derive(Default)]
+  struct CountingWaker(AtomicUsize);
+
+  impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+      self.0.fetch_add(1, Ordering::SeqCst);
+    }
+  }
+
+  fn poll<T: Future + Unpin>(future: &mut T, waker: &Arc<CountingWaker>) -> Poll<T::Output> {
+    let waker = Waker::from(Arc::clone(waker));
+    Pin::new(future).poll(&mut Context::from_waker(&waker))
+  }
+
+  #[test]
+  fn pending_until_called() {
+    let waker = Arc::new(CountingWaker::default());
+    let mut future = Callback::new_in(Ok, FakeBackend);
+    assert!(poll(&mut future, &waker).is_pending());
+    assert_eq!(waker.0.load(Ordering::SeqCst), 0);
+    future.as_closure().call(vec![JsValue::NULL]);
+    assert_eq!(waker.0.load(Ordering::SeqCst), 1);
+    assert!(matches!(poll(&mut future, &waker), Poll::Ready(Ok(_))));
+  }
+
+  #[test]
+  fn defaults_settle_in_backend() {
+    let waker = Arc::new(CountingWaker::default());
+    let mut future = Callback::default_in(FakeBackend);
+    future.as_closure().call(vec![JsValue::NULL]);
+    assert!(matches!(poll(&mut future, &waker), Poll::Ready(Ok(_))));
+    let mut future = CallbackPair::default_in(FakeBackend);
+    future.as_closures().1.call(vec![JsValue::NULL]);
+    assert!(matches!(poll(&mut future, &waker), Poll::Ready(Err(_))));
+    let mut future = Callback::default_node_in(FakeBackend);
+    assert!(poll(&mut future, &waker).is_pending()); // Not called, as `ErrorFirst` inspects its args with wasm
+  }
+
+  #[test]
+  fn convention_settles_in_backend() {
+    struct Rejects;
+
+    impl ErrorConvention for Rejects {
+      type Args = Args<2>;
+
+      fn settle(self, _args: Vec<JsValue>) -> Result<JsValue, JsValue> {
+        Err(JsValue::UNDEFINED)
+      }
+    }
+
+    let waker = Arc::new(CountingWaker::default());
+    let mut future = Callback::with_convention_in(Rejects, FakeBackend);
+    future.as_closure().call(vec![JsValue::NULL]);
+    assert!(matches!(poll(&mut future, &waker), Poll::Ready(Err(_))));
+  }
+
+  #[test]
+  fn called_with_missing_args() {
+    let waker = Arc::new(CountingWaker::default());
+    let seen = Rc::new(Cell::new(0));
+    let count = Rc::clone(&seen);
+    let mut future = Callback::new_in(
+      move |_a: JsValue, _b: JsValue, _c: JsValue| {
+        count.set(3);
+        Err(JsValue::UNDEFINED)
+      },
+      FakeBackend,
+    );
+    future.as_closure().call(vec![]);
+    assert_eq!(seen.get(), 3);
+    assert!(matches!(poll(&mut future, &waker), Poll::Ready(Err(_))));
+  }
+
+  #[test]
+  fn closure_dropped_after_call() {
+    let future = Callback::new_in(|| Ok(JsValue::TRUE), FakeBackend);
+    let weak_ref = Rc::downgrade(&future.as_closure());
+    assert!(weak_ref.upgrade().is_some()); // Assert weak_ref `Some`
+    weak_ref.upgrade().unwrap().call(vec![]);
+    assert!(weak_ref.upgrade().is_none()); // Assert weak_ref `None`
+  }
+
+  #[test]
+  #[should_panic]
+  fn called_twice_panics() {
+    let future = Callback::new_in(|| Ok(JsValue::TRUE), FakeBackend);
+    let closure = future.as_closure();
+    closure.call(vec![]);
+    closure.call(vec![]);
+  }
+
+  #[test]
+  fn pair_first_call_wins() {
+    let waker = Arc::new(CountingWaker::default());
+    let mut future = CallbackPair::new_in(|| Ok(JsValue::TRUE), |_a| Err(JsValue::FALSE), FakeBackend);
+    let closures = future.as_closures();
+    assert!(poll(&mut future, &waker).is_pending());
+    closures.1.call(vec![JsValue::NULL]);
+    assert!(!closures.0.is_called()); // The other function is left to throw if called, as with wasm
+    assert!(matches!(poll(&mut future, &waker), Poll::Ready(Err(_))));
+  }
+
+  #[test]
+  fn stream_yields_every_call() {
+    let waker = Arc::new(CountingWaker::default());
+    let mut stream = CallbackStream::new_in(Ok, FakeBackend);
+    let poll_next = |stream: &mut CallbackStream<_, _>| {
+      let waker = Waker::from(Arc::clone(&waker));
+      Pin::new(stream).poll_next(&mut Context::from_waker(&waker))
+    };
+    assert!(poll_next(&mut stream).is_pending());
+    stream.as_closure().call(vec![JsValue::TRUE]);
+    stream.as_closure().call(vec![JsValue::FALSE]);
+    assert_eq!(stream.as_closure().calls(), 2);
+    assert_eq!(waker.0.load(Ordering::SeqCst), 1); // Woken once, by the first call after polling
+    assert!(matches!(poll_next(&mut stream), Poll::Ready(Some(Ok(_)))));
+    assert!(matches!(poll_next(&mut stream), Poll::Ready(Some(Ok(_)))));
+    assert!(poll_next(&mut stream).is_pending());
+  }
+
+  #[test]
+  fn hook_runs_inside_call() {
+    let waker = Arc::new(CountingWaker::default());
+    let seen = Rc::new(Cell::new(0));
+    let count = Rc::clone(&seen);
+    let mut future = Callback::new_in(|_a: JsValue, _b: JsValue| Ok(JsValue::TRUE), FakeBackend)
+      .on_call(move |args| count.set(args.len()));
+    future.as_closure().call(vec![JsValue::NULL]);
+    assert_eq!(seen.get(), 1); // Assert the hook saw the args as called, before padding
+    assert!(matches!(poll(&mut future, &waker), Poll::Ready(Ok(_))));
+  }
+
+  #[test]
+  fn stream_hook_runs_for_every_call() {
+    let seen = Rc::new(Cell::new(0));
+    let count = Rc::clone(&seen);
+    let stream = CallbackStream::new_in(Ok, FakeBackend).on_call(move |_args| count.set(count.get() + 1));
+    stream.as_closure().call(vec![JsValue::TRUE]);
+    stream.as_closure().call(vec![JsValue::FALSE]);
+    assert_eq!(seen.get(), 2);
+  }
+
+  #[test]
+  fn returning_called_until_finished() {
+    let waker = Arc::new(CountingWaker::default());
+    let mut calls = 0;
+    let mut future = Callback::with_return_in(
+      move |_a: JsValue| {
+        calls += 1;
+        (JsValue::TRUE, Some(Ok(JsValue::NULL)).filter(|_| calls == 2))
+      },
+      FakeBackend,
+    );
+    let closure = future.as_closure();
+    closure.call(vec![]);
+    assert!(poll(&mut future, &waker).is_pending());
+    closure.call(vec![]);
+    assert_eq!(closure.calls(), 2);
+    assert!(matches!(poll(&mut future, &waker), Poll::Ready(Ok(_))));
+  }
+}
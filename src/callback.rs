@@ -1,17 +1,21 @@
+use crate::backend::{self, Backend, ReturnSignature, Signature, WasmBackend};
+use crate::convention::ErrorFirst;
+use crate::Returned;
 use core::cell::RefCell;
-use js_sys::Function;
+use js_sys::{Function, Promise};
 use std::fmt::Debug;
 use std::future::Future;
 use std::rc::Rc;
 use std::task::Poll;
 use std::task::Waker;
-use wasm_bindgen::prelude::Closure;
 use wasm_bindgen::JsValue;
 
 /// A `Callback<F>` is a wrapper around a `wasm_bindgen::prelude::Closure<F>` which supports TODO:
+///
+/// The backend `H` is only ever changed from the default in tests, see [`crate::FakeBackend`].
 #[derive(Debug)]
-pub struct Callback<F: 'static + ?Sized> {
-  inner: Rc<RefCell<CallbackInner<F>>>,
+pub struct Callback<F: 'static + ?Sized, H: Backend = WasmBackend> {
+  inner: Rc<RefCell<CallbackInner<F, H>>>,
 }
 
 impl<F: 'static + ?Sized> Callback<F> {
@@ -36,15 +40,113 @@ impl<F: 'static + ?Sized> Callback<F> {
     func
   }
 
-  pub fn as_closure(&self) -> Rc<Closure<F>> {
+  /// Converts this into a `Promise` which settles once the function is called, for handing the result back to
+  /// javascript instead of awaiting it in rust. The function must be retrieved before converting.
+  pub fn into_promise(self) -> Promise {
+    let inner = self.inner;
+    Promise::new(&mut |resolve, reject| {
+      let mut state = inner.borrow_mut();
+      match state.result.take() {
+        Some(Ok(data)) => drop(resolve.call1(&JsValue::NULL, &data)),
+        Some(Err(err)) => drop(reject.call1(&JsValue::NULL, &err)),
+        None => state.promise = Some((resolve, reject)),
+      }
+    })
+  }
+}
+
+impl<F: 'static + ?Sized, H: Backend> Callback<F, H> {
+  /// Like `new`, but for a backend other than the default.
+  pub fn new_in<X>(closure: X, _backend: H) -> Callback<F, H>
+  where
+    Self: From<X>,
+  {
+    Self::from(closure)
+  }
+
+  pub fn as_closure(&self) -> Rc<H::Function<F>> {
     Rc::clone(self.inner.borrow().cb.as_ref().unwrap())
   }
+
+  /// Deallocates the closure if it was never called, which would otherwise keep itself alive in case it still
+  /// is. Only for when javascript can no longer call it, such as a cleared timeout.
+  pub(crate) fn release(&self) {
+    drop(self.inner.borrow_mut().cb.take());
+  }
+
+  #[cfg(test)]
+  pub(crate) fn inner_ref(&self) -> std::rc::Weak<RefCell<CallbackInner<F, H>>> {
+    Rc::downgrade(&self.inner)
+  }
+
+  /// Adds a hook which runs synchronously inside the javascript call, with every argument it was called with,
+  /// before the Result is produced. Unlike the awaiting task, which only runs once the call has returned, it can
+  /// still act on the call, such as calling `preventDefault` on an event.
+  pub fn on_call<X: 'static + FnMut(&[JsValue])>(self, hook: X) -> Self {
+    self.set_hook(Hook::new(hook));
+    self
+  }
+
+  pub(crate) fn set_hook(&self, hook: Hook) {
+    self.inner.borrow_mut().hook = hook;
+  }
+}
+
+impl<F: 'static + ?Sized + Signature, H: Backend> Callback<F, H> {
+  /// Creates a callback whose Result is produced from every argument the function was called with, however many
+  /// `F` takes.
+  pub(crate) fn from_args<X>(cb: X) -> Callback<F, H>
+  where
+    X: 'static + FnOnce(Vec<JsValue>) -> Result<JsValue, JsValue>,
+  {
+    let inner = CallbackInner::new();
+    let state = Rc::clone(&inner);
+    let closure = H::once::<F>(Box::new(move |args| {
+      Hook::run(&state, |state| &mut state.hook, &args);
+      CallbackInner::finish(&state, cb(args))
+    }));
+    inner.borrow_mut().cb = Some(Rc::new(closure));
+    Callback { inner }
+  }
+}
+
+impl<F: 'static + ?Sized + ReturnSignature, H: Backend> Callback<F, H> {
+  /// Creates a callback whose function returns what `handler` returns to its caller, and finishes the first time
+  /// `handler` also produces a Result.
+  pub(crate) fn from_return<X>(mut handler: X) -> Callback<F, H>
+  where
+    X: 'static + FnMut(Vec<JsValue>) -> Returned,
+  {
+    let inner = CallbackInner::new();
+    let mut state = Some(Rc::clone(&inner));
+    let closure = H::returning::<F>(Box::new(move |args| {
+      if let Some(state) = &state {
+        Hook::run(state, |state| &mut state.hook, &args);
+      }
+      let (returned, result) = handler(args);
+      if let Some(result) = result {
+        if let Some(state) = state.take() {
+          CallbackInner::finish(&state, result);
+        }
+      }
+      returned
+    }));
+    inner.borrow_mut().cb = Some(Rc::new(closure));
+    Callback { inner }
+  }
 }
 
 /// The Default impl for Callback creates a single-arg callback, whose Result is always Ok.
 impl Default for Callback<dyn FnMut(JsValue)> {
   fn default() -> Self {
-    Self::from(|data| Ok(data))
+    Self::default_in(WasmBackend)
+  }
+}
+
+impl<H: Backend> Callback<dyn FnMut(JsValue), H> {
+  /// Like `default`, but for a backend other than the default.
+  pub fn default_in(_backend: H) -> Self {
+    Self::from(Ok)
   }
 }
 
@@ -52,17 +154,19 @@ impl Callback<dyn FnMut(JsValue, JsValue)> {
   /// Creates a node-style callback with the args `(err, data)`. If err is null or undefined,
   /// the Result is Ok(data). Otherwise, it is Err(err).
   pub fn default_node() -> Self {
-    Self::from(|err: JsValue, data: JsValue| {
-      if err.is_null() || err.is_undefined() {
-        return Ok(data);
-      }
-      Err(err)
-    })
+    Self::default_node_in(WasmBackend)
+  }
+}
+
+impl<H: Backend> Callback<dyn FnMut(JsValue, JsValue), H> {
+  /// Like `default_node`, but for a backend other than the default.
+  pub fn default_node_in(backend: H) -> Self {
+    Callback::with_convention_in(ErrorFirst, backend)
   }
 }
 
 /// Standard Future impl for Callback<T>
-impl<F: 'static + ?Sized> Future for Callback<F> {
+impl<F: 'static + ?Sized, H: Backend> Future for Callback<F, H> {
   type Output = Result<JsValue, JsValue>;
 
   fn poll(
@@ -84,14 +188,19 @@ macro_rules! from_impl {
   // a - The list of parameter types that FnMut A takes.
   // alist - The argument list of A.
   (($($a:ty),*), ($($alist:ident),*)) => {
-    impl<A> From<A> for Callback<dyn FnMut($($a,)*)>
+    impl<A, H> From<A> for Callback<dyn FnMut($($a,)*), H>
     where
       A: 'static + FnOnce($($a,)*) -> Result<JsValue, JsValue>,
+      H: Backend,
     {
       fn from(cb: A) -> Self {
         let inner = CallbackInner::new();
         let state = Rc::clone(&inner);
-        let closure = Closure::once(move |$($alist),*| CallbackInner::finish(&state, cb($($alist),*)));
+        let closure = H::once::<dyn FnMut($($a,)*)>(Box::new(move |args| {
+          Hook::run(&state, |state| &mut state.hook, &args);
+          let [$($alist),*] = backend::args(args);
+          CallbackInner::finish(&state, cb($($alist),*))
+        }));
         let ptr = Rc::new(closure);
         inner.borrow_mut().cb = Some(ptr);
         Callback { inner }
@@ -122,27 +231,43 @@ macro_rules! from_impl {
 from_impl!(a0 a1 a2 a3 a4 a5 a6); // Generate From impls for each list of arguments, up to 7.
 
 #[derive(Debug)]
-pub struct CallbackInner<F: 'static + ?Sized> {
-  cb: Option<Rc<Closure<F>>>,
+pub struct CallbackInner<F: 'static + ?Sized, H: Backend> {
+  cb: Option<Rc<H::Function<F>>>,
   result: Option<Result<JsValue, JsValue>>,
   task: Option<Waker>,
+  promise: Option<(Function, Function)>,
+  hook: Hook,
 }
 
-impl<F: 'static + ?Sized> CallbackInner<F> {
-  pub fn new() -> Rc<RefCell<CallbackInner<F>>> {
+impl<F: 'static + ?Sized, H: Backend> CallbackInner<F, H> {
+  pub fn new() -> Rc<RefCell<CallbackInner<F, H>>> {
     Rc::new(RefCell::new(CallbackInner {
       cb: None,
       task: None,
       result: None,
+      promise: None,
+      hook: Hook::default(),
     }))
   }
 
-  pub fn finish(state: &RefCell<CallbackInner<F>>, val: Result<JsValue, JsValue>) {
-    let task = {
+  pub fn finish(state: &RefCell<CallbackInner<F, H>>, val: Result<JsValue, JsValue>) {
+    let promise = {
       let mut state = state.borrow_mut();
       debug_assert!(state.result.is_none());
       debug_assert!(state.cb.is_some());
       drop(state.cb.take());
+      state.promise.take()
+    };
+    if let Some((resolve, reject)) = promise {
+      // Converted with `into_promise`, so there is no future left to wake.
+      let _ = match val {
+        Ok(data) => resolve.call1(&JsValue::NULL, &data),
+        Err(err) => reject.call1(&JsValue::NULL, &err),
+      };
+      return;
+    }
+    let task = {
+      let mut state = state.borrow_mut();
       state.result = Some(val);
       state.task.take()
     };
@@ -152,6 +277,33 @@ impl<F: 'static + ?Sized> CallbackInner<F> {
   }
 }
 
+/// A synchronous hook, run with the arguments of each call before its Result is produced. See `Callback::on_call`.
+#[derive(Default)]
+pub(crate) struct Hook(Option<HookFn>);
+
+type HookFn = Box<dyn FnMut(&[JsValue])>;
+
+impl Hook {
+  pub(crate) fn new<X: 'static + FnMut(&[JsValue])>(hook: X) -> Hook {
+    Hook(Some(Box::new(hook)))
+  }
+
+  /// Runs the hook `slot` selects from `state`. The hook is taken out while it runs, so it may touch `state` too.
+  pub(crate) fn run<S>(state: &RefCell<S>, slot: fn(&mut S) -> &mut Hook, args: &[JsValue]) {
+    let hook = slot(&mut state.borrow_mut()).0.take();
+    if let Some(mut hook) = hook {
+      hook(args);
+      slot(&mut state.borrow_mut()).0.get_or_insert(hook);
+    }
+  }
+}
+
+impl Debug for Hook {
+  fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    f.debug_tuple("Hook").field(&self.0.is_some()).finish()
+  }
+}
+
 #[cfg(test)]
 mod tests {
   use crate::Callback;
@@ -159,6 +311,7 @@ mod tests {
   use std::rc::Rc;
   use wasm_bindgen::prelude::*;
   use wasm_bindgen::JsCast;
+  use wasm_bindgen_futures::JsFuture;
   use wasm_bindgen_test::*;
   use web_sys::{window, IdbOpenDbRequest};
 
@@ -192,12 +345,12 @@ mod tests {
     let inner_ref = {
       let weak_ref = Rc::downgrade(&future.inner);
       req.set_onsuccess(Some(future.as_closure().as_ref().as_ref().unchecked_ref()));
-      assert_eq!(weak_ref.upgrade().is_some(), true); // Assert inner_ref `Some`
+      assert!(weak_ref.upgrade().is_some()); // Assert inner_ref `Some`
       weak_ref
     };
-    assert_eq!(inner_ref.upgrade().is_some(), true); // Assert inner_ref `Some`
+    assert!(inner_ref.upgrade().is_some()); // Assert inner_ref `Some`
     future.await.unwrap();
-    assert_eq!(inner_ref.upgrade().is_none(), true); // Assert inner_ref `None`
+    assert!(inner_ref.upgrade().is_none()); // Assert inner_ref `None`
   }
 
   #[wasm_bindgen_test]
@@ -214,12 +367,12 @@ mod tests {
     let resolve_ref = {
       let weak_ref = Rc::downgrade(&future.as_closure());
       req.set_onsuccess(Some(future.as_closure().as_ref().as_ref().unchecked_ref()));
-      assert_eq!(weak_ref.upgrade().is_some(), true); // Assert resolve_ref `Some`
+      assert!(weak_ref.upgrade().is_some()); // Assert resolve_ref `Some`
       weak_ref
     };
-    assert_eq!(resolve_ref.upgrade().is_some(), true); // Assert resolve_ref `Some`
+    assert!(resolve_ref.upgrade().is_some()); // Assert resolve_ref `Some`
     future.await.unwrap();
-    assert_eq!(resolve_ref.upgrade().is_none(), true); // Assert resolve_ref `None`
+    assert!(resolve_ref.upgrade().is_none()); // Assert resolve_ref `None`
   }
 
   #[wasm_bindgen(
@@ -238,7 +391,7 @@ mod tests {
     let future = Callback::default_node();
     extern_node_success_null(future.as_function().as_ref());
     let result = future.await;
-    assert_eq!(result.is_ok(), true); // Assert is `Ok`
+    assert!(result.is_ok()); // Assert is `Ok`
     assert_eq!(result.unwrap(), "success");
   }
 
@@ -247,7 +400,7 @@ mod tests {
     let future = Callback::default_node();
     extern_node_success_undefined(future.as_function().as_ref());
     let result = future.await;
-    assert_eq!(result.is_ok(), true); // Assert is `Ok`
+    assert!(result.is_ok()); // Assert is `Ok`
     assert_eq!(result.unwrap(), "success");
   }
 
@@ -256,7 +409,25 @@ mod tests {
     let future = Callback::default_node();
     extern_node_failure(future.as_function().as_ref());
     let result = future.await;
-    assert_eq!(result.is_err(), true); // Assert is `Err`
+    assert!(result.is_err()); // Assert is `Err`
+    assert_eq!(result.unwrap_err(), "failure");
+  }
+
+  #[wasm_bindgen_test]
+  async fn into_promise_settles_when_called() {
+    let future = Callback::default_node();
+    let func = future.as_function();
+    let promise = future.into_promise();
+    extern_node_success_null(&func);
+    let result = JsFuture::from(promise).await;
+    assert_eq!(result.unwrap(), "success");
+  }
+
+  #[wasm_bindgen_test]
+  async fn into_promise_after_called() {
+    let future = Callback::default_node();
+    extern_node_failure(future.as_function().as_ref());
+    let result = JsFuture::from(future.into_promise()).await;
     assert_eq!(result.unwrap_err(), "failure");
   }
 }
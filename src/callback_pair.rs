@@ -1,21 +1,25 @@
+use crate::backend::{self, Backend, WasmBackend};
+use crate::callback::Hook;
 use core::cell::RefCell;
-use js_sys::Function;
+use js_sys::{Function, Promise};
 use std::fmt::Debug;
 use std::future::Future;
 use std::rc::Rc;
 use std::task::Poll;
 use std::task::Waker;
-use wasm_bindgen::prelude::Closure;
 use wasm_bindgen::JsValue;
 
 /// A `CallbackPair<F>` is a wrapper around a `wasm_bindgen::prelude::Closure<F>` which supports TODO:
+///
+/// The backend `H` is only ever changed from the default in tests, see [`crate::FakeBackend`].
 #[derive(Debug)]
-pub struct CallbackPair<A, B>
+pub struct CallbackPair<A, B, H = WasmBackend>
 where
   A: 'static + ?Sized,
   B: 'static + ?Sized,
+  H: Backend,
 {
-  inner: Rc<RefCell<CallbackPairInner<A, B>>>,
+  inner: Rc<RefCell<CallbackPairInner<A, B, H>>>,
 }
 
 impl<A, B> CallbackPair<A, B>
@@ -54,24 +58,81 @@ where
     (left.into(), right.into())
   }
 
-  pub fn as_closures(&self) -> Rc<(Closure<A>, Closure<B>)> {
+  /// Converts this into a `Promise` which settles once the function is called, for handing the result back to
+  /// javascript instead of awaiting it in rust. The function must be retrieved before converting.
+  pub fn into_promise(self) -> Promise {
+    let inner = self.inner;
+    Promise::new(&mut |resolve, reject| {
+      let mut state = inner.borrow_mut();
+      match state.result.take() {
+        Some(Ok(data)) => drop(resolve.call1(&JsValue::NULL, &data)),
+        Some(Err(err)) => drop(reject.call1(&JsValue::NULL, &err)),
+        None => state.promise = Some((resolve, reject)),
+      }
+    })
+  }
+}
+
+impl<A, B, H> CallbackPair<A, B, H>
+where
+  A: 'static + ?Sized,
+  B: 'static + ?Sized,
+  H: Backend,
+{
+  /// Like `new`, but for a backend other than the default.
+  pub fn new_in<X, Y>(x: X, y: Y, _backend: H) -> CallbackPair<A, B, H>
+  where
+    Self: From<(X, Y)>,
+  {
+    Self::from((x, y))
+  }
+
+  pub fn as_closures(&self) -> Rc<Closures<A, B, H>> {
     Rc::clone(self.inner.borrow().cb.as_ref().unwrap())
   }
+
+  /// Like `Callback::release`, deallocates both closures if neither was called.
+  pub(crate) fn release(&self) {
+    drop(self.inner.borrow_mut().cb.take());
+  }
+
+  #[cfg(test)]
+  pub(crate) fn inner_ref(&self) -> std::rc::Weak<RefCell<CallbackPairInner<A, B, H>>> {
+    Rc::downgrade(&self.inner)
+  }
+
+  /// Like `Callback::on_call`, run by whichever function is called.
+  pub fn on_call<X: 'static + FnMut(&[JsValue])>(self, hook: X) -> Self {
+    self.set_hook(Hook::new(hook));
+    self
+  }
+
+  pub(crate) fn set_hook(&self, hook: Hook) {
+    self.inner.borrow_mut().hook = hook;
+  }
 }
 
 /// The Default impl for CallbackPair creates a pair of single-arg `(resolve, reject)` callbacks,
 /// similar to the javascript Promise contsructor.
 impl Default for CallbackPair<dyn FnMut(JsValue), dyn FnMut(JsValue)> {
   fn default() -> Self {
+    Self::default_in(WasmBackend)
+  }
+}
+
+impl<H: Backend> CallbackPair<dyn FnMut(JsValue), dyn FnMut(JsValue), H> {
+  /// Like `default`, but for a backend other than the default.
+  pub fn default_in(_backend: H) -> Self {
     Self::from((|data| Ok(data), |err| Err(err)))
   }
 }
 
 /// Standard impl of Future for CallbackPair.
-impl<A, B> Future for CallbackPair<A, B>
+impl<A, B, H> Future for CallbackPair<A, B, H>
 where
   A: 'static + ?Sized,
   B: 'static + ?Sized,
+  H: Backend,
 {
   type Output = Result<JsValue, JsValue>;
 
@@ -96,19 +157,28 @@ macro_rules! from_impl {
   // alist - The argument list of A.
   // blist - The argument list of B.
   (($($a:ty),*), ($($b:ty),*), ($($alist:ident),*), ($($blist:ident),*)) => {
-    impl<A, B> From<(A, B)> for CallbackPair<dyn FnMut($($a,)*), dyn FnMut($($b,)*)>
+    impl<A, B, H> From<(A, B)> for CallbackPair<dyn FnMut($($a,)*), dyn FnMut($($b,)*), H>
     where
       A: 'static + FnOnce($($a,)*) -> Result<JsValue, JsValue>,
       B: 'static + FnOnce($($b,)*) -> Result<JsValue, JsValue>,
+      H: Backend,
     {
       fn from(cb: (A, B)) -> Self {
         let inner = CallbackPairInner::new();
         let state = Rc::clone(&inner);
         let cb0 = cb.0;
-        let left = Closure::once(move |$($alist),*| CallbackPairInner::finish(&state, cb0($($alist),*)));
+        let left = H::once::<dyn FnMut($($a,)*)>(Box::new(move |args| {
+          Hook::run(&state, |state| &mut state.hook, &args);
+          let [$($alist),*] = backend::args(args);
+          CallbackPairInner::finish(&state, cb0($($alist),*))
+        }));
         let state = Rc::clone(&inner);
         let cb1 = cb.1;
-        let right = Closure::once(move |$($blist),*| CallbackPairInner::finish(&state, cb1($($blist),*)));
+        let right = H::once::<dyn FnMut($($b,)*)>(Box::new(move |args| {
+          Hook::run(&state, |state| &mut state.hook, &args);
+          let [$($blist),*] = backend::args(args);
+          CallbackPairInner::finish(&state, cb1($($blist),*))
+        }));
         let ptr = Rc::new((left, right));
         inner.borrow_mut().cb = Some(ptr);
         CallbackPair { inner }
@@ -158,36 +228,57 @@ macro_rules! from_impl {
 
 from_impl!(a0 a1 a2 a3 a4 a5 a6); // Generate From impls for every possible permutation of arguments in either callback, up to 7.
 
+/// The `(left, right)` pair of functions handed out by a CallbackPair.
+pub type Closures<A, B, H> = (<H as Backend>::Function<A>, <H as Backend>::Function<B>);
+
 #[derive(Debug)]
-pub struct CallbackPairInner<A, B>
+pub struct CallbackPairInner<A, B, H>
 where
   A: 'static + ?Sized,
   B: 'static + ?Sized,
+  H: Backend,
 {
-  cb: Option<Rc<(Closure<A>, Closure<B>)>>,
+  cb: Option<Rc<Closures<A, B, H>>>,
   result: Option<Result<JsValue, JsValue>>,
   task: Option<Waker>,
+  promise: Option<(Function, Function)>,
+  hook: Hook,
 }
 
-impl<A, B> CallbackPairInner<A, B>
+impl<A, B, H> CallbackPairInner<A, B, H>
 where
   A: 'static + ?Sized,
   B: 'static + ?Sized,
+  H: Backend,
 {
-  pub fn new() -> Rc<RefCell<CallbackPairInner<A, B>>> {
+  pub fn new() -> Rc<RefCell<CallbackPairInner<A, B, H>>> {
     Rc::new(RefCell::new(CallbackPairInner {
       cb: None,
       task: None,
       result: None,
+      promise: None,
+      hook: Hook::default(),
     }))
   }
 
-  pub fn finish(state: &RefCell<CallbackPairInner<A, B>>, val: Result<JsValue, JsValue>) {
-    let task = {
+  pub fn finish(state: &RefCell<CallbackPairInner<A, B, H>>, val: Result<JsValue, JsValue>) {
+    let promise = {
       let mut state = state.borrow_mut();
       debug_assert!(state.result.is_none());
       debug_assert!(state.cb.is_some());
       drop(state.cb.take());
+      state.promise.take()
+    };
+    if let Some((resolve, reject)) = promise {
+      // Converted with `into_promise`, so there is no future left to wake.
+      let _ = match val {
+        Ok(data) => resolve.call1(&JsValue::NULL, &data),
+        Err(err) => reject.call1(&JsValue::NULL, &err),
+      };
+      return;
+    }
+    let task = {
+      let mut state = state.borrow_mut();
       state.result = Some(val);
       state.task.take()
     };
@@ -202,6 +293,7 @@ mod tests {
   use crate::CallbackPair;
   use std::rc::Rc;
   use wasm_bindgen::JsCast;
+  use wasm_bindgen_futures::JsFuture;
   use wasm_bindgen_test::*;
   use web_sys::{window, IdbOpenDbRequest};
 
@@ -293,12 +385,12 @@ mod tests {
     let inner_ref = {
       let weak_ref = Rc::downgrade(&future.inner);
       req.set_onsuccess(Some(&functions.0));
-      assert_eq!(weak_ref.upgrade().is_some(), true); // Assert inner_ref `Some`
+      assert!(weak_ref.upgrade().is_some()); // Assert inner_ref `Some`
       weak_ref
     };
-    assert_eq!(inner_ref.upgrade().is_some(), true); // Assert inner_ref `Some`
+    assert!(inner_ref.upgrade().is_some()); // Assert inner_ref `Some`
     future.await.unwrap();
-    assert_eq!(inner_ref.upgrade().is_none(), true); // Assert inner_ref `None`
+    assert!(inner_ref.upgrade().is_none()); // Assert inner_ref `None`
   }
 
   #[wasm_bindgen_test]
@@ -316,12 +408,12 @@ mod tests {
       let weak_ref = Rc::downgrade(&closures);
       req.set_onsuccess(Some(closures.0.as_ref().as_ref().unchecked_ref()));
       req.set_onerror(Some(closures.1.as_ref().as_ref().unchecked_ref()));
-      assert_eq!(weak_ref.upgrade().is_some(), true); // Assert resolve_ref `Some`
+      assert!(weak_ref.upgrade().is_some()); // Assert resolve_ref `Some`
       weak_ref
     };
-    assert_eq!(wref.upgrade().is_some(), true); // Assert resolve_ref `Some`
+    assert!(wref.upgrade().is_some()); // Assert resolve_ref `Some`
     future.await.unwrap();
-    assert_eq!(wref.upgrade().is_none(), true); // Assert resolve_ref `None`
+    assert!(wref.upgrade().is_none()); // Assert resolve_ref `None`
   }
 
   #[wasm_bindgen_test]
@@ -332,7 +424,7 @@ mod tests {
       .set_timeout_with_callback_and_timeout_and_arguments_0(future.as_functions().0.as_ref(), 200)
       .unwrap();
     let result = future.await;
-    assert_eq!(result.is_ok(), true); // Assert is `Ok`
+    assert!(result.is_ok()); // Assert is `Ok`
   }
 
   #[wasm_bindgen_test]
@@ -343,6 +435,19 @@ mod tests {
       .set_timeout_with_callback_and_timeout_and_arguments_0(future.as_functions().1.as_ref(), 200)
       .unwrap();
     let result = future.await;
-    assert_eq!(result.is_err(), true); // Assert is `Err`
+    assert!(result.is_err()); // Assert is `Err`
+  }
+
+  #[wasm_bindgen_test]
+  async fn into_promise_right_reject() {
+    let future = CallbackPair::default();
+    let (_resolve, reject) = future.as_functions();
+    let promise = future.into_promise();
+    web_sys::window()
+      .unwrap()
+      .set_timeout_with_callback_and_timeout_and_arguments_0(&reject, 200)
+      .unwrap();
+    let result = JsFuture::from(promise).await;
+    assert!(result.is_err()); // Assert is `Err`
   }
 }
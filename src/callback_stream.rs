@@ -0,0 +1,223 @@
+use crate::backend::{self, Backend, WasmBackend};
+use crate::callback::Hook;
+use core::cell::RefCell;
+use futures_core::Stream;
+use js_sys::Function;
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::rc::{Rc, Weak};
+use std::task::Poll;
+use std::task::Waker;
+use wasm_bindgen::JsValue;
+
+/// A `CallbackStream<F>` is the reusable counterpart of a [`crate::Callback`]. Its function may be called any
+/// number of times, and the Result of each call is yielded in order.
+///
+/// Unlike a `Callback`, the function only holds a weak reference to the stream, so it must not be called after
+/// the stream is dropped.
+#[derive(Debug)]
+pub struct CallbackStream<F: 'static + ?Sized, H: Backend = WasmBackend> {
+  inner: Rc<RefCell<CallbackStreamInner<F, H>>>,
+}
+
+impl<F: 'static + ?Sized> CallbackStream<F> {
+  pub fn new<X>(closure: X) -> CallbackStream<F>
+  where
+    Self: From<X>,
+  {
+    Self::from(closure)
+  }
+
+  pub fn as_function(&self) -> Function {
+    let js_func: JsValue = self
+      .inner
+      .borrow()
+      .cb
+      .as_ref()
+      .unwrap()
+      .as_ref()
+      .as_ref()
+      .into();
+    let func: Function = js_func.into();
+    func
+  }
+}
+
+impl<F: 'static + ?Sized, H: Backend> CallbackStream<F, H> {
+  /// Like `new`, but for a backend other than the default.
+  pub fn new_in<X>(closure: X, _backend: H) -> CallbackStream<F, H>
+  where
+    Self: From<X>,
+  {
+    Self::from(closure)
+  }
+
+  pub fn as_closure(&self) -> Rc<H::Function<F>> {
+    Rc::clone(self.inner.borrow().cb.as_ref().unwrap())
+  }
+
+  /// Like `Callback::on_call`, run for every call.
+  pub fn on_call<X: 'static + FnMut(&[JsValue])>(self, hook: X) -> Self {
+    self.set_hook(Hook::new(hook));
+    self
+  }
+
+  pub(crate) fn set_hook(&self, hook: Hook) {
+    self.inner.borrow_mut().hook = hook;
+  }
+}
+
+/// The Default impl for CallbackStream creates a single-arg callback, whose Results are always Ok.
+impl Default for CallbackStream<dyn FnMut(JsValue)> {
+  fn default() -> Self {
+    Self::from(Ok)
+  }
+}
+
+/// Standard Stream impl for CallbackStream<T>
+impl<F: 'static + ?Sized, H: Backend> Stream for CallbackStream<F, H> {
+  type Item = Result<JsValue, JsValue>;
+
+  fn poll_next(
+    self: std::pin::Pin<&mut Self>,
+    cx: &mut std::task::Context<'_>,
+  ) -> std::task::Poll<Option<Self::Item>> {
+    let mut inner = self.inner.borrow_mut();
+    if let Some(val) = inner.results.pop_front() {
+      return Poll::Ready(Some(val));
+    }
+    inner.task = Some(cx.waker().clone());
+    Poll::Pending
+  }
+}
+
+/// A utility macro for generating every possible implementation of `From<A> for CallbackStream`.
+macro_rules! from_impl {
+  // The main arm of this macro. Generates a single From impl for CallbackStream.
+  // a - The list of parameter types that FnMut A takes.
+  // alist - The argument list of A.
+  (($($a:ty),*), ($($alist:ident),*)) => {
+    impl<A, H> From<A> for CallbackStream<dyn FnMut($($a,)*), H>
+    where
+      A: 'static + FnMut($($a,)*) -> Result<JsValue, JsValue>,
+      H: Backend,
+    {
+      fn from(mut cb: A) -> Self {
+        let inner = CallbackStreamInner::new();
+        let state = Rc::downgrade(&inner);
+        let closure = H::many::<dyn FnMut($($a,)*)>(Box::new(move |args| {
+          if let Some(state) = state.upgrade() {
+            Hook::run(&state, |state| &mut state.hook, &args);
+          }
+          let [$($alist),*] = backend::args(args);
+          CallbackStreamInner::push(&state, cb($($alist),*))
+        }));
+        let ptr = Rc::new(closure);
+        inner.borrow_mut().cb = Some(ptr);
+        CallbackStream { inner }
+      }
+    }
+  };
+  // Shorthand for the main arm. Based on the argument list, generate the parameter types (always JsValue) for that list.
+  (($($a:ident,)*)) => {
+    from_impl!(($(from_impl!(@rep $a JsValue)),*), ($($a),*));
+  };
+  // For a list of identifiers, recursively generates a From impl for that list and every list with less args.
+  ($head:ident $($tail:tt)*) => {
+    // Generate a From impl for the full set of arguments.
+    from_impl!(($head, $($tail,)*));
+    // Recurse inwards, generating the same definitions with one less argument.
+    from_impl!($($tail)*);
+  };
+  // Utility for replacing anything with a type.
+  (@rep $_t:tt $sub:ty) => {
+    $sub
+  };
+  // Empty arms for handling the end of recursion.
+  () => {
+    from_impl!(());
+  };
+}
+
+from_impl!(a0 a1 a2 a3 a4 a5 a6); // Generate From impls for each list of arguments, up to 7.
+
+#[derive(Debug)]
+pub struct CallbackStreamInner<F: 'static + ?Sized, H: Backend> {
+  cb: Option<Rc<H::Function<F>>>,
+  results: VecDeque<Result<JsValue, JsValue>>,
+  task: Option<Waker>,
+  hook: Hook,
+}
+
+impl<F: 'static + ?Sized, H: Backend> CallbackStreamInner<F, H> {
+  pub fn new() -> Rc<RefCell<CallbackStreamInner<F, H>>> {
+    Rc::new(RefCell::new(CallbackStreamInner {
+      cb: None,
+      task: None,
+      results: VecDeque::new(),
+      hook: Hook::default(),
+    }))
+  }
+
+  /// Queues a result. The function holds a weak reference, since the stream owns it and never finishes.
+  pub fn push(state: &Weak<RefCell<CallbackStreamInner<F, H>>>, val: Result<JsValue, JsValue>) {
+    let state = match state.upgrade() {
+      Some(state) => state,
+      None => return,
+    };
+    let task = {
+      let mut state = state.borrow_mut();
+      state.results.push_back(val);
+      state.task.take()
+    };
+    if let Some(task) = task {
+      task.wake()
+    }
+  }
+}
+
+/// Waits for the next item of a stream, without depending on a full futures utility crate.
+#[cfg(test)]
+pub(crate) async fn next<S: Stream + Unpin>(stream: &mut S) -> Option<S::Item> {
+  std::future::poll_fn(|cx| std::pin::Pin::new(&mut *stream).poll_next(cx)).await
+}
+
+#[cfg(test)]
+mod tests {
+  use super::next;
+  use crate::CallbackStream;
+  use wasm_bindgen::JsValue;
+  use wasm_bindgen_test::*;
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  #[wasm_bindgen_test]
+  async fn yields_every_call_in_order() {
+    let mut stream = CallbackStream::default();
+    let func = stream.as_function();
+    func.call1(&JsValue::NULL, &"a".into()).unwrap();
+    func.call1(&JsValue::NULL, &"b".into()).unwrap();
+    assert_eq!(next(&mut stream).await.unwrap().unwrap(), "a");
+    assert_eq!(next(&mut stream).await.unwrap().unwrap(), "b");
+  }
+
+  #[wasm_bindgen_test]
+  async fn yields_errors() {
+    let mut stream = CallbackStream::new(|a: JsValue, b: JsValue| if a.is_null() { Ok(b) } else { Err(a) });
+    let func = stream.as_function();
+    func.call2(&JsValue::NULL, &"failure".into(), &JsValue::NULL).unwrap();
+    func.call2(&JsValue::NULL, &JsValue::NULL, &"success".into()).unwrap();
+    assert_eq!(next(&mut stream).await.unwrap().unwrap_err(), "failure");
+    assert_eq!(next(&mut stream).await.unwrap().unwrap(), "success");
+  }
+
+  #[wasm_bindgen_test]
+  async fn wakes_when_called_later() {
+    let mut stream = CallbackStream::default();
+    web_sys::window()
+      .unwrap()
+      .set_timeout_with_callback_and_timeout_and_arguments_0(&stream.as_function(), 100)
+      .unwrap();
+    assert!(next(&mut stream).await.unwrap().is_ok());
+  }
+}
@@ -0,0 +1,120 @@
+use js_sys::{Array, Error, Function};
+use std::future::Future;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::spawn_local;
+
+#[wasm_bindgen(inline_js = "export function variadic(f) { return function(...args) { return f(args); }; }")]
+extern "C" {
+  /// Wraps a function taking a single array into a function taking any number of arguments.
+  pub(crate) fn variadic(f: &Function) -> Function;
+}
+
+/// The inverse of `Callback::default_node`. Creates a javascript function with the args `(...args, cb)` which
+/// calls the given closure with `args`, and calls `cb(null, data)` if the future it returns is Ok(data), or
+/// `cb(err)` if it is Err(err). Since a null or undefined err would be read as success, it is replaced with an
+/// `Error`.
+///
+/// If the function is called without a trailing callback, it throws a `TypeError` instead of calling the closure.
+/// Anything thrown by the callback itself is discarded.
+/// The returned function is never deallocated, so it is best suited for long-lived exports.
+pub fn callbackify<F, Fut>(f: F) -> Function
+where
+  F: 'static + Fn(Array) -> Fut,
+  Fut: 'static + Future<Output = Result<JsValue, JsValue>>,
+{
+  let closure: Closure<dyn FnMut(Array) -> Result<(), JsValue>> = Closure::wrap(Box::new(move |args: Array| {
+    let cb: Function = args
+      .pop()
+      .dyn_into()
+      .map_err(|_| js_sys::TypeError::new("The last argument must be a callback function"))?;
+    let future = f(args);
+    spawn_local(async move {
+      // Unlike node's own `util.callbackify`, anything thrown by the callback is caught and discarded, as there is
+      // no caller left to report it to.
+      let _ = match future.await {
+        Ok(data) => cb.call2(&JsValue::NULL, &JsValue::NULL, &data),
+        Err(err) => cb.call1(&JsValue::NULL, &node_error(err)),
+      };
+    });
+    Ok(())
+  }));
+  variadic(closure.into_js_value().unchecked_ref())
+}
+
+/// Ensures an error is not mistaken for success by a node-style callback.
+fn node_error(err: JsValue) -> JsValue {
+  if err.is_null() || err.is_undefined() {
+    return Error::new("Future failed with a null or undefined error").into();
+  }
+  err
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{callbackify, Callback};
+  use js_sys::{Array, Promise};
+  use wasm_bindgen::prelude::*;
+  use wasm_bindgen_futures::JsFuture;
+  use wasm_bindgen_test::*;
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  #[wasm_bindgen(inline_js = "export function throwing_callback() {
+      let called;
+      const promise = new Promise((resolve) => { called = resolve; });
+      return [(_err, data) => { called(data); throw new Error('thrown'); }, promise];
+    };")]
+  extern "C" {
+    fn throwing_callback() -> Array;
+  }
+
+  #[wasm_bindgen_test]
+  async fn calls_back_with_data() {
+    let func = callbackify(|args: Array| async move { Ok(args.get(1)) });
+    let future = Callback::default_node();
+    func
+      .call3(&JsValue::NULL, &"a".into(), &"b".into(), future.as_function().as_ref())
+      .unwrap();
+    let result = future.await;
+    assert!(result.is_ok()); // Assert is `Ok`
+    assert_eq!(result.unwrap(), "b");
+  }
+
+  #[wasm_bindgen_test]
+  async fn calls_back_with_err() {
+    let func = callbackify(|_args| async move { Err("failure".into()) });
+    let future = Callback::default_node();
+    func.call1(&JsValue::NULL, future.as_function().as_ref()).unwrap();
+    let result = future.await;
+    assert!(result.is_err()); // Assert is `Err`
+    assert_eq!(result.unwrap_err(), "failure");
+  }
+
+  #[wasm_bindgen_test]
+  async fn null_err_is_not_success() {
+    let func = callbackify(|_args| async move { Err(JsValue::NULL) });
+    let future = Callback::default_node();
+    func.call1(&JsValue::NULL, future.as_function().as_ref()).unwrap();
+    let result = future.await;
+    assert!(result.unwrap_err().is_instance_of::<js_sys::Error>());
+  }
+
+  #[wasm_bindgen_test]
+  fn throws_without_callback() {
+    let func = callbackify(|_args| async move { Ok(JsValue::UNDEFINED) });
+    assert!(func.call1(&JsValue::NULL, &"a".into()).is_err());
+  }
+
+  #[wasm_bindgen_test]
+  async fn callback_exception_is_discarded() {
+    let func = callbackify(|args: Array| async move { Ok(args.get(0)) });
+    let pair = throwing_callback();
+    func.call2(&JsValue::NULL, &"a".into(), &pair.get(0)).unwrap();
+    assert_eq!(JsFuture::from(pair.get(1).unchecked_into::<Promise>()).await.unwrap(), "a");
+    // The executor carries on after the exception, so a later call still completes.
+    let future = Callback::default_node();
+    func.call2(&JsValue::NULL, &"b".into(), future.as_function().as_ref()).unwrap();
+    assert_eq!(future.await.unwrap(), "b");
+  }
+}
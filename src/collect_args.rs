@@ -0,0 +1,120 @@
+use crate::{Callback, CallbackPair, Signature};
+use js_sys::Array;
+use wasm_bindgen::JsValue;
+
+/// Selects a callback signature by its number of arguments `N`, for [`Callback::collect_args`].
+#[derive(Debug)]
+pub struct Args<const N: usize>;
+
+/// Implemented by [`Args`] for every arity a callback may be created with, up to 7.
+pub trait Arity {
+  /// The `dyn FnMut(JsValue, ..)` taking this many arguments.
+  type Signature: 'static + ?Sized + Signature;
+  /// A function taking this many arguments, returning them as an `Array`.
+  type Collect: 'static;
+  /// The function collecting this many arguments, as `Ok`.
+  const COLLECT: Self::Collect;
+  /// Like `COLLECT`, but as `Err`.
+  const REJECT: Self::Collect;
+}
+
+/// A utility macro for generating an implementation of `Arity` for every argument list.
+macro_rules! arity_impl {
+  ($n:literal; $($alist:ident)*) => {
+    impl Arity for Args<$n> {
+      type Signature = dyn FnMut($(arity_impl!(@rep $alist JsValue)),*);
+      type Collect = fn($(arity_impl!(@rep $alist JsValue)),*) -> Result<JsValue, JsValue>;
+      const COLLECT: Self::Collect = |$($alist),*| {
+        let args: [JsValue; $n] = [$($alist),*];
+        Ok(args.iter().collect::<Array>().into())
+      };
+      const REJECT: Self::Collect = |$($alist),*| {
+        let args: [JsValue; $n] = [$($alist),*];
+        Err(args.iter().collect::<Array>().into())
+      };
+    }
+  };
+  // Utility for replacing anything with a type.
+  (@rep $_t:tt $sub:ty) => {
+    $sub
+  };
+}
+
+arity_impl!(0;);
+arity_impl!(1; a0);
+arity_impl!(2; a0 a1);
+arity_impl!(3; a0 a1 a2);
+arity_impl!(4; a0 a1 a2 a3);
+arity_impl!(5; a0 a1 a2 a3 a4);
+arity_impl!(6; a0 a1 a2 a3 a4 a5);
+arity_impl!(7; a0 a1 a2 a3 a4 a5 a6);
+
+impl Callback<dyn FnMut(JsValue)> {
+  /// Creates a callback taking `N` arguments, which resolves with all of them as an `Array`, so a multi-argument
+  /// callback needs no closure just to bundle them. Arguments the caller leaves out are `undefined`.
+  pub fn collect_args<const N: usize>() -> Callback<<Args<N> as Arity>::Signature>
+  where
+    Args<N>: Arity,
+    Callback<<Args<N> as Arity>::Signature>: From<<Args<N> as Arity>::Collect>,
+  {
+    Callback::from(Args::<N>::COLLECT)
+  }
+}
+
+impl CallbackPair<dyn FnMut(JsValue), dyn FnMut(JsValue)> {
+  /// Like `Callback::collect_args`, for a `(resolve, reject)` pair taking `N` and `M` arguments respectively. The
+  /// pair resolves or rejects with an `Array` of the arguments either was called with.
+  pub fn collect_args<const N: usize, const M: usize>(
+  ) -> CallbackPair<<Args<N> as Arity>::Signature, <Args<M> as Arity>::Signature>
+  where
+    Args<N>: Arity,
+    Args<M>: Arity,
+    CallbackPair<<Args<N> as Arity>::Signature, <Args<M> as Arity>::Signature>:
+      From<(<Args<N> as Arity>::Collect, <Args<M> as Arity>::Collect)>,
+  {
+    CallbackPair::from((Args::<N>::COLLECT, Args::<M>::REJECT))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Callback, CallbackPair};
+  use js_sys::Array;
+  use wasm_bindgen::prelude::*;
+  use wasm_bindgen::JsCast;
+  use wasm_bindgen_test::*;
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  #[wasm_bindgen_test]
+  async fn resolves_with_every_arg() {
+    let future = Callback::collect_args::<3>();
+    future
+      .as_function()
+      .call3(&JsValue::NULL, &"a".into(), &"b".into(), &"c".into())
+      .unwrap();
+    let args: Array = future.await.unwrap().dyn_into().unwrap();
+    assert_eq!(args.length(), 3);
+    assert_eq!(args.get(0), "a");
+    assert_eq!(args.get(2), "c");
+  }
+
+  #[wasm_bindgen_test]
+  async fn missing_args_are_undefined() {
+    let future = Callback::collect_args::<2>();
+    future.as_function().call1(&JsValue::NULL, &"a".into()).unwrap();
+    let args: Array = future.await.unwrap().dyn_into().unwrap();
+    assert_eq!(args.length(), 2);
+    assert!(args.get(1).is_undefined());
+  }
+
+  #[wasm_bindgen_test]
+  async fn pair_rejects_with_every_arg() {
+    let future = CallbackPair::collect_args::<0, 2>();
+    let (_resolve, reject) = future.as_functions();
+    reject.call2(&JsValue::NULL, &"code".into(), &"message".into()).unwrap();
+    let args: Array = future.await.unwrap_err().dyn_into().unwrap();
+    assert_eq!(args.get(0), "code");
+    assert_eq!(args.get(1), "message");
+  }
+}
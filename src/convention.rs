@@ -0,0 +1,183 @@
+//! The ways a callback may report failure, for [`Callback::with_convention`].
+use crate::backend::{self, Backend, WasmBackend};
+use crate::{Args, Arity, Callback};
+use js_sys::Reflect;
+use wasm_bindgen::JsValue;
+
+/// Decides whether a callback succeeded from the arguments it was called with. Implement this for conventions
+/// other than the built-in ones.
+pub trait ErrorConvention: 'static {
+  /// The number of arguments the callback takes, as [`Args`].
+  type Args: Arity;
+
+  /// Produces the Result of the callback from every argument it was called with.
+  fn settle(self, args: Vec<JsValue>) -> Result<JsValue, JsValue>;
+}
+
+fn is_nullish(value: &JsValue) -> bool {
+  value.is_null() || value.is_undefined()
+}
+
+/// `(err, data)`, as used by node. Succeeds with `data` if `err` is null or undefined, otherwise fails with `err`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ErrorFirst;
+
+impl ErrorConvention for ErrorFirst {
+  type Args = Args<2>;
+
+  fn settle(self, args: Vec<JsValue>) -> Result<JsValue, JsValue> {
+    let [err, data] = backend::args(args);
+    if is_nullish(&err) {
+      return Ok(data);
+    }
+    Err(err)
+  }
+}
+
+/// `(data, err)`. Like [`ErrorFirst`], with the arguments swapped.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ErrorLast;
+
+impl ErrorConvention for ErrorLast {
+  type Args = Args<2>;
+
+  fn settle(self, args: Vec<JsValue>) -> Result<JsValue, JsValue> {
+    let [data, err] = backend::args(args);
+    ErrorFirst.settle(vec![err, data])
+  }
+}
+
+/// `(success, value)`. Succeeds with `value` if `success` is truthy, otherwise fails with `value`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SuccessFlag;
+
+impl ErrorConvention for SuccessFlag {
+  type Args = Args<2>;
+
+  fn settle(self, args: Vec<JsValue>) -> Result<JsValue, JsValue> {
+    let [success, value] = backend::args(args);
+    if success.is_truthy() {
+      return Ok(value);
+    }
+    Err(value)
+  }
+}
+
+/// `(result)`, as used by chrome extension APIs. Fails with `chrome.runtime.lastError` if it is set while the
+/// callback runs, which is also the only time it can be checked.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChromeLastError;
+
+impl ErrorConvention for ChromeLastError {
+  type Args = Args<1>;
+
+  fn settle(self, args: Vec<JsValue>) -> Result<JsValue, JsValue> {
+    let [result] = backend::args(args);
+    let mut err: JsValue = js_sys::global().into();
+    for key in ["chrome", "runtime", "lastError"] {
+      if !err.is_object() {
+        return Ok(result);
+      }
+      err = Reflect::get(&err, &key.into())?;
+    }
+    if is_nullish(&err) {
+      return Ok(result);
+    }
+    Err(err)
+  }
+}
+
+/// `({ error, result })`. Fails with `error` if it is set, otherwise succeeds with `result`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ResultObject;
+
+impl ErrorConvention for ResultObject {
+  type Args = Args<1>;
+
+  fn settle(self, args: Vec<JsValue>) -> Result<JsValue, JsValue> {
+    let [object] = backend::args(args);
+    let err = Reflect::get(&object, &"error".into())?;
+    if is_nullish(&err) {
+      return Reflect::get(&object, &"result".into());
+    }
+    Err(err)
+  }
+}
+
+impl Callback<dyn FnMut(JsValue)> {
+  /// Creates a callback taking as many arguments as `convention` does, whose Result it decides.
+  pub fn with_convention<C: ErrorConvention>(convention: C) -> Callback<<C::Args as Arity>::Signature> {
+    Callback::with_convention_in(convention, WasmBackend)
+  }
+}
+
+impl<H: Backend> Callback<dyn FnMut(JsValue), H> {
+  /// Like `with_convention`, but for a backend other than the default.
+  pub fn with_convention_in<C: ErrorConvention>(
+    convention: C,
+    _backend: H,
+  ) -> Callback<<C::Args as Arity>::Signature, H> {
+    Callback::from_args(move |args| convention.settle(args))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{ChromeLastError, ErrorLast, ResultObject, SuccessFlag};
+  use crate::Callback;
+  use js_sys::{Object, Reflect};
+  use wasm_bindgen::prelude::*;
+  use wasm_bindgen_test::*;
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  #[wasm_bindgen(inline_js = "export function call_with(cb, a, b) { setTimeout(() => cb(a, b)); };
+    export function with_last_error(err, cb) { globalThis.chrome = { runtime: { lastError: err } }; cb('result'); delete globalThis.chrome; };")]
+  extern "C" {
+    fn call_with(cb: &js_sys::Function, a: &JsValue, b: &JsValue);
+    fn with_last_error(err: &JsValue, cb: &js_sys::Function);
+  }
+
+  #[wasm_bindgen_test]
+  async fn error_last() {
+    let future = Callback::with_convention(ErrorLast);
+    call_with(&future.as_function(), &"data".into(), &JsValue::NULL);
+    assert_eq!(future.await.unwrap(), "data");
+    let future = Callback::with_convention(ErrorLast);
+    call_with(&future.as_function(), &JsValue::UNDEFINED, &"failure".into());
+    assert_eq!(future.await.unwrap_err(), "failure");
+  }
+
+  #[wasm_bindgen_test]
+  async fn success_flag() {
+    let future = Callback::with_convention(SuccessFlag);
+    call_with(&future.as_function(), &JsValue::TRUE, &"data".into());
+    assert_eq!(future.await.unwrap(), "data");
+    let future = Callback::with_convention(SuccessFlag);
+    call_with(&future.as_function(), &JsValue::FALSE, &"failure".into());
+    assert_eq!(future.await.unwrap_err(), "failure");
+  }
+
+  #[wasm_bindgen_test]
+  async fn chrome_last_error() {
+    let future = Callback::with_convention(ChromeLastError);
+    with_last_error(&JsValue::UNDEFINED, &future.as_function());
+    assert_eq!(future.await.unwrap(), "result");
+    let future = Callback::with_convention(ChromeLastError);
+    with_last_error(&"failure".into(), &future.as_function());
+    assert_eq!(future.await.unwrap_err(), "failure");
+  }
+
+  #[wasm_bindgen_test]
+  async fn result_object() {
+    let object = Object::new();
+    Reflect::set(&object, &"result".into(), &"data".into()).unwrap();
+    let future = Callback::with_convention(ResultObject);
+    call_with(&future.as_function(), &object, &JsValue::UNDEFINED);
+    assert_eq!(future.await.unwrap(), "data");
+    Reflect::set(&object, &"error".into(), &"failure".into()).unwrap();
+    let future = Callback::with_convention(ResultObject);
+    call_with(&future.as_function(), &object, &JsValue::UNDEFINED);
+    assert_eq!(future.await.unwrap_err(), "failure");
+  }
+}
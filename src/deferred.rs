@@ -0,0 +1,209 @@
+use core::cell::RefCell;
+use js_sys::Function;
+use std::fmt::Debug;
+use std::future::Future;
+use std::rc::{Rc, Weak};
+use std::task::Poll;
+use std::task::Waker;
+use wasm_bindgen::prelude::Closure;
+use wasm_bindgen::{JsCast, JsValue};
+
+/// A `Deferred` is a one-shot future which may be settled from either rust, through a [`Resolver`], or
+/// javascript, through the functions returned by [`Deferred::as_functions`], similar to the javascript
+/// `Promise.withResolvers`. Whichever side settles it first wins, and every later attempt is ignored.
+#[derive(Debug)]
+pub struct Deferred {
+  inner: Rc<RefCell<DeferredInner>>,
+}
+
+impl Deferred {
+  pub fn new() -> Deferred {
+    Deferred {
+      inner: DeferredInner::new(),
+    }
+  }
+
+  /// Returns a handle which can settle this `Deferred` from rust.
+  pub fn resolver(&self) -> Resolver {
+    Resolver {
+      inner: Rc::clone(&self.inner),
+    }
+  }
+
+  /// Returns a `(resolve, reject)` pair of javascript functions which settle this `Deferred`. The functions
+  /// remain callable even after this `Deferred` and its `Resolver`s are dropped, doing nothing once it is
+  /// settled, so they are only deallocated along with the javascript functions, if at all.
+  pub fn as_functions(&self) -> (Function, Function) {
+    DeferredInner::closures(&self.inner)
+  }
+}
+
+impl Default for Deferred {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+/// Standard impl of Future for Deferred.
+impl Future for Deferred {
+  type Output = Result<JsValue, JsValue>;
+
+  fn poll(
+    self: std::pin::Pin<&mut Self>,
+    cx: &mut std::task::Context<'_>,
+  ) -> std::task::Poll<Self::Output> {
+    let mut inner = self.inner.borrow_mut();
+    if let Some(val) = inner.result.take() {
+      return Poll::Ready(val);
+    }
+    inner.task = Some(cx.waker().clone());
+    Poll::Pending
+  }
+}
+
+/// A cloneable handle for settling a [`Deferred`] from rust.
+#[derive(Debug, Clone)]
+pub struct Resolver {
+  inner: Rc<RefCell<DeferredInner>>,
+}
+
+impl Resolver {
+  /// Resolves the `Deferred` with `Ok(value)`. Returns `false` if it had already been settled.
+  pub fn resolve(&self, value: JsValue) -> bool {
+    DeferredInner::finish(&self.inner, Ok(value))
+  }
+
+  /// Rejects the `Deferred` with `Err(value)`. Returns `false` if it had already been settled.
+  pub fn reject(&self, value: JsValue) -> bool {
+    DeferredInner::finish(&self.inner, Err(value))
+  }
+
+  /// Returns true once the `Deferred` has been settled by either side.
+  pub fn is_settled(&self) -> bool {
+    self.inner.borrow().settled
+  }
+}
+
+
+#[derive(Debug)]
+pub struct DeferredInner {
+  cb: Option<(Function, Function)>,
+  result: Option<Result<JsValue, JsValue>>,
+  settled: bool,
+  task: Option<Waker>,
+}
+
+impl DeferredInner {
+  pub fn new() -> Rc<RefCell<DeferredInner>> {
+    Rc::new(RefCell::new(DeferredInner {
+      cb: None,
+      task: None,
+      settled: false,
+      result: None,
+    }))
+  }
+
+  /// Lazily creates the javascript closures, so a `Deferred` which is only ever settled from rust never
+  /// allocates any. The closures are handed over to javascript, and only hold a weak reference to the state, so
+  /// calling them after it is dropped does nothing.
+  fn closures(state: &Rc<RefCell<DeferredInner>>) -> (Function, Function) {
+    if let Some(cb) = state.borrow().cb.as_ref() {
+      return cb.clone();
+    }
+    let weak = Rc::downgrade(state);
+    let left: Closure<dyn FnMut(JsValue)> =
+      Closure::wrap(Box::new(move |val| DeferredInner::finish_weak(&weak, Ok(val))));
+    let weak = Rc::downgrade(state);
+    let right: Closure<dyn FnMut(JsValue)> =
+      Closure::wrap(Box::new(move |val| DeferredInner::finish_weak(&weak, Err(val))));
+    let cb = (left.into_js_value().unchecked_into(), right.into_js_value().unchecked_into());
+    state.borrow_mut().cb = Some((Function::clone(&cb.0), Function::clone(&cb.1)));
+    cb
+  }
+
+  fn finish_weak(state: &Weak<RefCell<DeferredInner>>, val: Result<JsValue, JsValue>) {
+    if let Some(state) = state.upgrade() {
+      DeferredInner::finish(&state, val);
+    }
+  }
+
+  pub fn finish(state: &RefCell<DeferredInner>, val: Result<JsValue, JsValue>) -> bool {
+    let task = {
+      let mut state = state.borrow_mut();
+      if state.settled {
+        return false;
+      }
+      state.settled = true;
+      state.result = Some(val);
+      state.task.take()
+    };
+    if let Some(task) = task {
+      task.wake()
+    }
+    true
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::Deferred;
+  use wasm_bindgen_test::*;
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  #[wasm_bindgen_test]
+  async fn rust_resolve() {
+    let future = Deferred::new();
+    let resolver = future.resolver();
+    assert!(resolver.resolve("success".into()));
+    let result = future.await;
+    assert!(result.is_ok()); // Assert is `Ok`
+    assert_eq!(result.unwrap(), "success");
+  }
+
+  #[wasm_bindgen_test]
+  async fn rust_reject() {
+    let future = Deferred::new();
+    future.resolver().clone().reject("failure".into());
+    let result = future.await;
+    assert!(result.is_err()); // Assert is `Err`
+    assert_eq!(result.unwrap_err(), "failure");
+  }
+
+  #[wasm_bindgen_test]
+  async fn js_resolve() {
+    let future = Deferred::new();
+    web_sys::window()
+      .unwrap()
+      .set_timeout_with_callback_and_timeout_and_arguments_0(future.as_functions().0.as_ref(), 200)
+      .unwrap();
+    let result = future.await;
+    assert!(result.is_ok()); // Assert is `Ok`
+  }
+
+  #[wasm_bindgen_test]
+  async fn first_settle_wins() {
+    let future = Deferred::new();
+    let resolver = future.resolver();
+    let (resolve, _reject) = future.as_functions();
+    assert!(resolver.reject("rust".into()));
+    resolve.call1(&wasm_bindgen::JsValue::NULL, &"js".into()).unwrap(); // Ignored, but must not throw
+    assert!(!resolver.resolve("rust again".into()));
+    assert!(resolver.is_settled());
+    let result = future.await;
+    assert_eq!(result.unwrap_err(), "rust");
+  }
+
+  #[wasm_bindgen_test]
+  async fn js_call_after_dropped_is_ignored() {
+    let future = Deferred::new();
+    let resolver = future.resolver();
+    let (resolve, reject) = future.as_functions();
+    assert!(resolver.resolve("rust".into()));
+    drop(resolver);
+    assert_eq!(future.await.unwrap(), "rust");
+    // The `Deferred` and every `Resolver` are gone, so these must do nothing, rather than throw.
+    resolve.call1(&wasm_bindgen::JsValue::NULL, &"js".into()).unwrap();
+    reject.call1(&wasm_bindgen::JsValue::NULL, &"js".into()).unwrap();
+  }
+}
@@ -0,0 +1,124 @@
+use crate::{Backend, Callback, CallbackPair};
+use serde::de::DeserializeOwned;
+use std::fmt;
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::Poll;
+use wasm_bindgen::JsValue;
+
+/// The error produced by a [`Deserialize`] future.
+#[derive(Debug)]
+pub enum DeserializeError {
+  /// The callback itself resolved with `Err`, which is passed through untouched.
+  Rejected(JsValue),
+  /// The callback resolved with `Ok`, but the value could not be deserialized.
+  Deserialize(serde_wasm_bindgen::Error),
+}
+
+impl fmt::Display for DeserializeError {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      DeserializeError::Rejected(err) => write!(f, "callback rejected with {:?}", err),
+      DeserializeError::Deserialize(err) => write!(f, "failed to deserialize callback result: {}", err),
+    }
+  }
+}
+
+impl std::error::Error for DeserializeError {}
+
+/// A future adapter which deserializes the `Ok` value of a `Callback` or `CallbackPair` into `T`.
+#[derive(Debug)]
+pub struct Deserialize<C, T> {
+  future: C,
+  _marker: PhantomData<fn() -> T>,
+}
+
+impl<C, T> Future for Deserialize<C, T>
+where
+  C: Future<Output = Result<JsValue, JsValue>> + Unpin,
+  T: DeserializeOwned,
+{
+  type Output = Result<T, DeserializeError>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+    let this = self.get_mut();
+    match Pin::new(&mut this.future).poll(cx) {
+      Poll::Ready(Ok(val)) => {
+        Poll::Ready(serde_wasm_bindgen::from_value(val).map_err(DeserializeError::Deserialize))
+      }
+      Poll::Ready(Err(err)) => Poll::Ready(Err(DeserializeError::Rejected(err))),
+      Poll::Pending => Poll::Pending,
+    }
+  }
+}
+
+impl<F: 'static + ?Sized, H: Backend> Callback<F, H> {
+  /// Converts this into a future which deserializes the `Ok` value into `T`.
+  pub fn deserialize<T: DeserializeOwned>(self) -> Deserialize<Self, T> {
+    Deserialize {
+      future: self,
+      _marker: PhantomData,
+    }
+  }
+}
+
+impl<A, B, H> CallbackPair<A, B, H>
+where
+  A: 'static + ?Sized,
+  B: 'static + ?Sized,
+  H: Backend,
+{
+  /// Converts this into a future which deserializes the `Ok` value into `T`.
+  pub fn deserialize<T: DeserializeOwned>(self) -> Deserialize<Self, T> {
+    Deserialize {
+      future: self,
+      _marker: PhantomData,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::{Callback, CallbackPair, DeserializeError};
+  use serde::Deserialize;
+  use wasm_bindgen::prelude::*;
+  use wasm_bindgen_test::*;
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  #[derive(Debug, Deserialize, PartialEq)]
+  struct Position {
+    x: f64,
+    y: f64,
+  }
+
+  #[wasm_bindgen(inline_js = "export function extern_position(cb) { cb({ x: 1, y: 2 }) };")]
+  extern "C" {
+    fn extern_position(cb: &js_sys::Function);
+  }
+
+  #[wasm_bindgen_test]
+  async fn deserializes_ok_value() {
+    let future = Callback::default();
+    extern_position(future.as_function().as_ref());
+    let result = future.deserialize::<Position>().await;
+    assert_eq!(result.unwrap(), Position { x: 1.0, y: 2.0 });
+  }
+
+  #[wasm_bindgen_test]
+  async fn conversion_failure_is_distinct() {
+    let future = Callback::default();
+    extern_position(future.as_function().as_ref());
+    let result = future.deserialize::<String>().await;
+    assert!(matches!(result, Err(DeserializeError::Deserialize(_))));
+  }
+
+  #[wasm_bindgen_test]
+  async fn rejection_is_passed_through() {
+    let future = CallbackPair::default();
+    extern_position(&future.as_functions().1);
+    let result = future.deserialize::<Position>().await;
+    assert!(matches!(result, Err(DeserializeError::Rejected(_))));
+  }
+}
@@ -0,0 +1,125 @@
+use crate::{Callback, CallbackPair};
+use js_sys::{Array, Function, Reflect};
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+use wasm_bindgen::{JsCast, JsValue};
+
+type Pair = CallbackPair<dyn FnMut(JsValue), dyn FnMut(JsValue)>;
+
+/// The future returned by [`call_dual`].
+pub struct DualCall<F: 'static + ?Sized> {
+  callback: Callback<F>,
+  promise: Option<Pair>,
+  thrown: Option<JsValue>,
+}
+
+/// Calls `func` with `args` and `callback` appended, for functions which may either call the callback or return
+/// a promise. If the return value is a thenable, it is raced against `callback`, and the Result of whichever
+/// settles first is used, while the other is ignored. If `func` throws, the error is returned immediately.
+///
+/// The losing side cannot be detached, since javascript may still call it: its closures stay allocated until it
+/// does, and leak if it never does, such as a thenable which never settles.
+pub fn call_dual<F>(this: &JsValue, func: &Function, args: &Array, callback: Callback<F>) -> DualCall<F>
+where
+  F: 'static + ?Sized,
+{
+  let args = args.concat(&Array::of1(&callback.as_function()));
+  let (promise, thrown) = match func.apply(this, &args).and_then(|value| then(&value)) {
+    Ok(promise) => (promise, None),
+    Err(err) => (None, Some(err)),
+  };
+  DualCall {
+    callback,
+    promise,
+    thrown,
+  }
+}
+
+/// Attaches a `(resolve, reject)` pair to `value` if it is a thenable. A `then` which throws is treated as a
+/// rejection, as a promise would.
+fn then(value: &JsValue) -> Result<Option<Pair>, JsValue> {
+  if !value.is_object() {
+    return Ok(None);
+  }
+  let then: Function = match Reflect::get(value, &"then".into())?.dyn_into() {
+    Ok(then) => then,
+    Err(_) => return Ok(None),
+  };
+  let future = CallbackPair::default();
+  let (resolve, reject) = future.as_functions();
+  then.call2(value, &resolve, &reject)?;
+  Ok(Some(future))
+}
+
+impl<F: 'static + ?Sized> Future for DualCall<F> {
+  type Output = Result<JsValue, JsValue>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+    let this = self.get_mut();
+    if let Some(err) = this.thrown.take() {
+      return Poll::Ready(Err(err));
+    }
+    if let Poll::Ready(result) = Pin::new(&mut this.callback).poll(cx) {
+      this.promise = None;
+      return Poll::Ready(result);
+    }
+    match this.promise.as_mut().map(|promise| Pin::new(promise).poll(cx)) {
+      Some(Poll::Ready(result)) => Poll::Ready(result),
+      _ => Poll::Pending,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::call_dual;
+  use crate::Callback;
+  use js_sys::{Array, Function};
+  use wasm_bindgen::prelude::*;
+  use wasm_bindgen_test::*;
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  #[wasm_bindgen(inline_js = "export function promise_style() { return (value, _cb) => Promise.resolve(value); };
+    export function callback_style() { return (value, cb) => { setTimeout(() => cb(null, value)); }; };
+    export function both_styles() { return (value, cb) => { cb(null, 'callback'); return Promise.resolve(value); }; };
+    export function rejecting() { return (value, _cb) => Promise.reject(value); };
+    export function throwing() { return (value, _cb) => { throw value; }; };")]
+  extern "C" {
+    fn promise_style() -> Function;
+    fn callback_style() -> Function;
+    fn both_styles() -> Function;
+    fn rejecting() -> Function;
+    fn throwing() -> Function;
+  }
+
+  async fn call(func: Function) -> Result<JsValue, JsValue> {
+    call_dual(&JsValue::NULL, &func, &Array::of1(&"value".into()), Callback::default_node()).await
+  }
+
+  #[wasm_bindgen_test]
+  async fn resolves_from_promise() {
+    assert_eq!(call(promise_style()).await.unwrap(), "value");
+  }
+
+  #[wasm_bindgen_test]
+  async fn resolves_from_callback() {
+    assert_eq!(call(callback_style()).await.unwrap(), "value");
+  }
+
+  #[wasm_bindgen_test]
+  async fn first_to_settle_wins() {
+    assert_eq!(call(both_styles()).await.unwrap(), "callback");
+  }
+
+  #[wasm_bindgen_test]
+  async fn rejects_from_promise() {
+    assert_eq!(call(rejecting()).await.unwrap_err(), "value");
+  }
+
+  #[wasm_bindgen_test]
+  async fn throw_is_returned() {
+    assert_eq!(call(throwing()).await.unwrap_err(), "value");
+  }
+}
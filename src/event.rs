@@ -0,0 +1,429 @@
+use crate::callback::Hook;
+use crate::{Callback, CallbackPair, CallbackStream};
+use core::cell::RefCell;
+use futures_core::Stream;
+use js_sys::{Function, Reflect};
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::task::Poll;
+use wasm_bindgen::{JsCast, JsValue, UnwrapThrowExt};
+use web_sys::{AbortSignal, AddEventListenerOptions, EventTarget};
+
+/// Removes the listeners an EventFuture was attached with. Shared with the listeners themselves when they must
+/// detach each other synchronously.
+type Detach = Rc<RefCell<Option<Box<dyn FnOnce()>>>>;
+
+fn detach(detach: &Detach) {
+  let detach = detach.borrow_mut().take();
+  if let Some(detach) = detach {
+    detach()
+  }
+}
+
+/// A future attached as event listeners, which removes them once it settles or is dropped, so they never
+/// outlive the closures they call. If it is dropped before settling, the closures are released as well.
+pub struct EventFuture<C> {
+  future: C,
+  detach: Detach,
+  release: fn(&C),
+}
+
+impl<F: 'static + ?Sized> EventFuture<Callback<F>> {
+  pub(crate) fn new(future: Callback<F>, detach: impl FnOnce() + 'static) -> Self {
+    EventFuture {
+      future,
+      detach: Rc::new(RefCell::new(Some(Box::new(detach)))),
+      release: Callback::release,
+    }
+  }
+}
+
+impl EventFuture<CallbackPair<dyn FnMut(JsValue), dyn FnMut(JsValue)>> {
+  /// Creates a `(resolve, reject)` pair, which `attach` registers as listeners before returning how to remove
+  /// them again. Both are removed as soon as either is called, since calling the other would then throw.
+  pub(crate) fn pair<D>(attach: impl FnOnce(&Function, &Function) -> D) -> Self
+  where
+    D: FnOnce() + 'static,
+  {
+    Self::pair_with(Ok, Err, attach)
+  }
+
+  /// Like `pair`, but the Result is produced by `ok` or `err` from the argument the listener was called with.
+  pub(crate) fn pair_with<X, Y, D>(ok: X, err: Y, attach: impl FnOnce(&Function, &Function) -> D) -> Self
+  where
+    X: 'static + FnOnce(JsValue) -> Result<JsValue, JsValue>,
+    Y: 'static + FnOnce(JsValue) -> Result<JsValue, JsValue>,
+    D: FnOnce() + 'static,
+  {
+    let shared: Detach = Rc::default();
+    let (left, right) = (Rc::clone(&shared), Rc::clone(&shared));
+    let future = CallbackPair::new(
+      move |data| {
+        detach(&left);
+        ok(data)
+      },
+      move |data| {
+        detach(&right);
+        err(data)
+      },
+    );
+    let (resolve, reject) = future.as_functions();
+    shared.replace(Some(Box::new(attach(&resolve, &reject))));
+    EventFuture {
+      future,
+      detach: shared,
+      release: CallbackPair::release,
+    }
+  }
+}
+
+impl<F: 'static + ?Sized> EventFuture<Callback<F>> {
+  /// Adds a hook which runs synchronously while the event is dispatched, see `Callback::on_call`.
+  pub fn on_call<X: 'static + FnMut(&[JsValue])>(self, hook: X) -> Self {
+    self.future.set_hook(Hook::new(hook));
+    self
+  }
+}
+
+impl<A: 'static + ?Sized, B: 'static + ?Sized> EventFuture<CallbackPair<A, B>> {
+  /// Adds a hook which runs synchronously while either event is dispatched, see `Callback::on_call`.
+  pub fn on_call<X: 'static + FnMut(&[JsValue])>(self, hook: X) -> Self {
+    self.future.set_hook(Hook::new(hook));
+    self
+  }
+}
+
+impl<C> Future for EventFuture<C>
+where
+  C: Future + Unpin,
+{
+  type Output = C::Output;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+    let this = self.get_mut();
+    let result = Pin::new(&mut this.future).poll(cx);
+    if result.is_ready() {
+      detach(&this.detach);
+    }
+    result
+  }
+}
+
+impl<C> Drop for EventFuture<C> {
+  fn drop(&mut self) {
+    detach(&self.detach);
+    (self.release)(&self.future);
+  }
+}
+
+impl Callback<dyn FnMut(JsValue)> {
+  /// Creates a callback which resolves with the next `event` dispatched on `target`. Unlike setting an `on*`
+  /// handler, the listener is added with `addEventListener`, so it does not replace any other handler.
+  pub fn once_event(target: &EventTarget, event: &str) -> EventFuture<Self> {
+    let future = Self::default();
+    let listener = future.as_function();
+    let options = AddEventListenerOptions::new();
+    options.set_once(true);
+    target
+      .add_event_listener_with_callback_and_add_event_listener_options(event, &listener, &options)
+      .unwrap_throw();
+    let (target, event) = (target.clone(), event.to_owned());
+    EventFuture::new(future, move || {
+      let _ = target.remove_event_listener_with_callback(&event, &listener);
+    })
+  }
+}
+
+impl CallbackPair<dyn FnMut(JsValue), dyn FnMut(JsValue)> {
+  /// Creates a callback pair which resolves with the next `ok` event dispatched on `target`, or rejects with the
+  /// next `err` event, whichever comes first, such as `load` and `error`.
+  pub fn from_events(target: &EventTarget, ok: &str, err: &str) -> EventFuture<Self> {
+    EventFuture::pair(|resolve, reject| {
+      target.add_event_listener_with_callback(ok, resolve).unwrap_throw();
+      target.add_event_listener_with_callback(err, reject).unwrap_throw();
+      let (target, ok, err) = (target.clone(), ok.to_owned(), err.to_owned());
+      let (resolve, reject) = (resolve.clone(), reject.clone());
+      move || {
+        let _ = target.remove_event_listener_with_callback(&ok, &resolve);
+        let _ = target.remove_event_listener_with_callback(&err, &reject);
+      }
+    })
+  }
+}
+
+/// The `abort` event of an EventStream's `signal`.
+type Aborted = EventFuture<Callback<dyn FnMut(JsValue)>>;
+
+/// A stream of every `event` dispatched on an `EventTarget`, whose listener is removed when the stream is
+/// dropped. If the listener is removed by the browser instead, by aborting its `signal` or after the first event
+/// with `once`, the stream ends.
+pub struct EventStream {
+  stream: CallbackStream<dyn FnMut(JsValue)>,
+  detach: Detach,
+  aborted: Option<Aborted>,
+  once: bool,
+  ended: bool,
+}
+
+impl EventStream {
+  pub fn new(target: &EventTarget, event: &str) -> EventStream {
+    Self::with_options(target, event, &AddEventListenerOptions::new())
+  }
+
+  /// Like `new`, honouring the `capture`, `once`, `passive` and `signal` options of `addEventListener`.
+  pub fn with_options(target: &EventTarget, event: &str, options: &AddEventListenerOptions) -> EventStream {
+    let stream = CallbackStream::default();
+    let listener = stream.as_function();
+    target
+      .add_event_listener_with_callback_and_add_event_listener_options(event, &listener, options)
+      .unwrap_throw();
+    // A listener is identified by its capture flag as well, so removing it needs the same flag.
+    let capture = option(options, "capture").is_truthy();
+    let signal = option(options, "signal").dyn_into::<AbortSignal>().ok();
+    let (target, event) = (target.clone(), event.to_owned());
+    let detach = move || {
+      let _ = target.remove_event_listener_with_callback_and_bool(&event, &listener, capture);
+    };
+    EventStream {
+      stream,
+      detach: Rc::new(RefCell::new(Some(Box::new(detach)))),
+      ended: signal.as_ref().is_some_and(|signal| signal.aborted()),
+      aborted: signal.map(|signal| Callback::once_event(&signal, "abort")),
+      once: option(options, "once").is_truthy(),
+    }
+  }
+
+  /// Adds a hook which runs synchronously while each event is dispatched, see `Callback::on_call`.
+  pub fn on_call<X: 'static + FnMut(&[JsValue])>(self, hook: X) -> Self {
+    self.stream.set_hook(Hook::new(hook));
+    self
+  }
+}
+
+fn option(options: &AddEventListenerOptions, name: &str) -> JsValue {
+  Reflect::get(options, &name.into()).unwrap_or(JsValue::UNDEFINED)
+}
+
+impl Stream for EventStream {
+  type Item = Result<JsValue, JsValue>;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<Self::Item>> {
+    let this = self.get_mut();
+    if this.ended {
+      return Poll::Ready(None);
+    }
+    // Events dispatched before the signal was aborted are still yielded first.
+    if let Poll::Ready(item) = Pin::new(&mut this.stream).poll_next(cx) {
+      this.ended = this.once;
+      return Poll::Ready(item);
+    }
+    match this.aborted.as_mut().map(|aborted| Pin::new(aborted).poll(cx)) {
+      Some(Poll::Ready(_)) => {
+        this.ended = true;
+        Poll::Ready(None)
+      }
+      _ => Poll::Pending,
+    }
+  }
+}
+
+impl Drop for EventStream {
+  fn drop(&mut self) {
+    detach(&self.detach)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::callback_stream::next;
+  use crate::{Callback, CallbackPair, EventStream};
+  use wasm_bindgen::prelude::*;
+  use wasm_bindgen::JsCast;
+  use wasm_bindgen_test::*;
+  use web_sys::{AbortController, AddEventListenerOptions, Event, EventInit, EventTarget};
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  #[wasm_bindgen(
+    inline_js = "export function counting_target() {
+      const target = new EventTarget();
+      const listeners = new Map();
+      const add = target.addEventListener.bind(target);
+      const remove = target.removeEventListener.bind(target);
+      // A listener is identified by its capture flag as well, so it is only removed with the same flag.
+      const capture = (opts) => typeof opts === 'boolean' ? opts : !!(opts && opts.capture);
+      target.addEventListener = (type, cb, opts) => {
+        listeners.set(cb, (listeners.get(cb) || new Set()).add(capture(opts)));
+        add(type, cb, opts);
+      };
+      target.removeEventListener = (type, cb, opts) => {
+        listeners.get(cb)?.delete(capture(opts));
+        remove(type, cb, opts);
+      };
+      target.listeners = () => [...listeners.values()].reduce((count, flags) => count + flags.size, 0);
+      return target;
+    }"
+  )]
+  extern "C" {
+    #[wasm_bindgen(extends = EventTarget)]
+    type CountingTarget;
+    fn counting_target() -> CountingTarget;
+    #[wasm_bindgen(method)]
+    fn listeners(this: &CountingTarget) -> u32;
+  }
+
+  #[wasm_bindgen_test]
+  async fn resolves_with_event() {
+    let target = counting_target();
+    let future = Callback::once_event(&target, "load");
+    target.dispatch_event(&Event::new("load").unwrap()).unwrap();
+    let result = future.await;
+    assert!(result.unwrap().is_instance_of::<Event>());
+    assert_eq!(target.listeners(), 0);
+  }
+
+  #[wasm_bindgen_test]
+  fn listener_removed_when_dropped() {
+    let target = counting_target();
+    let future = Callback::once_event(&target, "load");
+    assert_eq!(target.listeners(), 1);
+    drop(future);
+    assert_eq!(target.listeners(), 0);
+  }
+
+  #[wasm_bindgen_test]
+  fn inner_dropped_when_dropped() {
+    let target = counting_target();
+    let future = Callback::once_event(&target, "load");
+    let inner_ref = future.future.inner_ref();
+    assert!(inner_ref.upgrade().is_some()); // Assert inner_ref `Some`
+    drop(future);
+    assert!(inner_ref.upgrade().is_none()); // Assert inner_ref `None`
+  }
+
+  #[wasm_bindgen_test]
+  async fn other_events_ignored() {
+    let target = counting_target();
+    let future = Callback::once_event(&target, "load");
+    target.dispatch_event(&Event::new("error").unwrap()).unwrap();
+    target.dispatch_event(&Event::new("load").unwrap()).unwrap();
+    let event: Event = future.await.unwrap().unchecked_into();
+    assert_eq!(event.type_(), "load");
+  }
+
+  fn cancelable(event: &str) -> Event {
+    let init = EventInit::new();
+    init.set_cancelable(true);
+    Event::new_with_event_init_dict(event, &init).unwrap()
+  }
+
+  #[wasm_bindgen_test]
+  async fn hook_prevents_default_while_dispatching() {
+    let target = counting_target();
+    let future = Callback::once_event(&target, "submit").on_call(|args| {
+      args[0].unchecked_ref::<Event>().prevent_default();
+    });
+    assert!(!target.dispatch_event(&cancelable("submit")).unwrap()); // Assert default was prevented
+    assert!(future.await.is_ok()); // Assert is `Ok`
+  }
+
+  #[wasm_bindgen_test]
+  async fn pair_resolves_with_ok_event() {
+    let target = counting_target();
+    let future = CallbackPair::from_events(&target, "success", "error");
+    assert_eq!(target.listeners(), 2);
+    target.dispatch_event(&Event::new("success").unwrap()).unwrap();
+    assert_eq!(target.listeners(), 0); // Detached synchronously, before the future is polled
+    let result = future.await;
+    assert!(result.is_ok()); // Assert is `Ok`
+  }
+
+  #[wasm_bindgen_test]
+  async fn pair_rejects_with_err_event() {
+    let target = counting_target();
+    let future = CallbackPair::from_events(&target, "success", "error");
+    target.dispatch_event(&Event::new("error").unwrap()).unwrap();
+    target.dispatch_event(&Event::new("success").unwrap()).unwrap();
+    let event: Event = future.await.unwrap_err().unchecked_into();
+    assert_eq!(event.type_(), "error");
+  }
+
+  #[wasm_bindgen_test]
+  fn pair_inner_dropped_when_dropped() {
+    let target = counting_target();
+    let future = CallbackPair::from_events(&target, "open", "close");
+    let inner_ref = future.future.inner_ref();
+    assert!(inner_ref.upgrade().is_some()); // Assert inner_ref `Some`
+    drop(future);
+    assert!(inner_ref.upgrade().is_none()); // Assert inner_ref `None`
+  }
+
+  #[wasm_bindgen_test]
+  fn pair_listeners_removed_when_dropped() {
+    let target = counting_target();
+    drop(CallbackPair::from_events(&target, "open", "close"));
+    assert_eq!(target.listeners(), 0);
+  }
+
+  #[wasm_bindgen_test]
+  async fn stream_yields_every_event() {
+    let target = counting_target();
+    let mut stream = EventStream::new(&target, "message");
+    target.dispatch_event(&Event::new("message").unwrap()).unwrap();
+    target.dispatch_event(&Event::new("message").unwrap()).unwrap();
+    assert!(next(&mut stream).await.unwrap().is_ok());
+    assert!(next(&mut stream).await.unwrap().is_ok());
+    drop(stream);
+    assert_eq!(target.listeners(), 0);
+  }
+
+  #[wasm_bindgen_test]
+  async fn stream_hook_prevents_default_for_every_event() {
+    let target = counting_target();
+    let mut stream = EventStream::new(&target, "submit").on_call(|args| {
+      args[0].unchecked_ref::<Event>().prevent_default();
+    });
+    assert!(!target.dispatch_event(&cancelable("submit")).unwrap());
+    assert!(!target.dispatch_event(&cancelable("submit")).unwrap());
+    assert!(next(&mut stream).await.unwrap().is_ok());
+    assert!(next(&mut stream).await.unwrap().is_ok());
+  }
+
+  #[wasm_bindgen_test]
+  fn stream_capture_listener_removed_when_dropped() {
+    let target = counting_target();
+    let options = AddEventListenerOptions::new();
+    options.set_capture(true);
+    let stream = EventStream::with_options(&target, "message", &options);
+    assert_eq!(target.listeners(), 1);
+    drop(stream);
+    assert_eq!(target.listeners(), 0);
+  }
+
+  #[wasm_bindgen_test]
+  async fn stream_honours_signal() {
+    let target = EventTarget::new().unwrap();
+    let controller = AbortController::new().unwrap();
+    let options = AddEventListenerOptions::new();
+    options.set_signal(&controller.signal());
+    let mut stream = EventStream::with_options(&target, "message", &options);
+    target.dispatch_event(&Event::new("message").unwrap()).unwrap();
+    controller.abort();
+    target.dispatch_event(&Event::new("message").unwrap()).unwrap();
+    assert!(next(&mut stream).await.unwrap().is_ok());
+    assert!(next(&mut stream).await.is_none()); // Assert the stream ended
+  }
+
+  #[wasm_bindgen_test]
+  async fn stream_ends_after_once() {
+    let target = EventTarget::new().unwrap();
+    let options = AddEventListenerOptions::new();
+    options.set_once(true);
+    let mut stream = EventStream::with_options(&target, "message", &options);
+    target.dispatch_event(&Event::new("message").unwrap()).unwrap();
+    target.dispatch_event(&Event::new("message").unwrap()).unwrap();
+    assert!(next(&mut stream).await.unwrap().is_ok());
+    assert!(next(&mut stream).await.is_none()); // Assert the stream ended
+  }
+}
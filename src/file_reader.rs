@@ -0,0 +1,148 @@
+//! Futures for reading a `Blob` with a `FileReader`, which abort the read if dropped early.
+use crate::{CallbackPair, EventFuture};
+use js_sys::ArrayBuffer;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+use wasm_bindgen::{JsCast, JsValue};
+use web_sys::{Blob, FileReader};
+
+type Pair = EventFuture<CallbackPair<dyn FnMut(JsValue), dyn FnMut(JsValue)>>;
+
+/// A read in progress, which resolves with the reader's `result` as `T`, or rejects with its `error`. If the read
+/// could not be started, it rejects with that error instead.
+pub struct Read<T> {
+  future: Option<Pair>,
+  thrown: Option<JsValue>,
+  convert: fn(JsValue) -> T,
+}
+
+impl<T> Future for Read<T> {
+  type Output = Result<T, JsValue>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+    let this = self.get_mut();
+    if let Some(err) = this.thrown.take() {
+      return Poll::Ready(Err(err));
+    }
+    match this.future.as_mut().map(|future| Pin::new(future).poll(cx)) {
+      Some(Poll::Ready(result)) => Poll::Ready(result.map(this.convert)),
+      _ => Poll::Pending,
+    }
+  }
+}
+
+type Start = fn(&FileReader, &Blob) -> Result<(), JsValue>;
+
+fn read<T>(blob: &Blob, start: Start, convert: fn(JsValue) -> T) -> Read<T> {
+  match FileReader::new() {
+    Ok(reader) => read_with(&reader, blob, start, convert),
+    Err(err) => Read {
+      future: None,
+      thrown: Some(err),
+      convert,
+    },
+  }
+}
+
+/// Starts a read of `blob` on `reader` with `start`, then attaches `onload`, `onerror` and `onabort`, which are
+/// only ever called from a later task. The handlers are cleared once the read settles, and if the future is
+/// dropped while still loading, the read is aborted.
+fn read_with<T>(reader: &FileReader, blob: &Blob, start: Start, convert: fn(JsValue) -> T) -> Read<T> {
+  if let Err(err) = start(reader, blob) {
+    return Read {
+      future: None,
+      thrown: Some(err),
+      convert,
+    };
+  }
+  let (ok, err) = (reader.clone(), reader.clone());
+  let future = EventFuture::pair_with(
+    move |_event| ok.result(),
+    move |event| Err(err.error().map(JsValue::from).unwrap_or(event)),
+    |resolve, reject| {
+      reader.set_onload(Some(resolve));
+      reader.set_onerror(Some(reject));
+      reader.set_onabort(Some(reject));
+      let reader = reader.clone();
+      move || {
+        reader.set_onload(None);
+        reader.set_onerror(None);
+        reader.set_onabort(None);
+        if reader.ready_state() == FileReader::LOADING {
+          reader.abort();
+        }
+      }
+    },
+  );
+  Read {
+    future: Some(future),
+    thrown: None,
+    convert,
+  }
+}
+
+/// Reads the contents of `blob` into an `ArrayBuffer`.
+pub fn read_as_array_buffer(blob: &Blob) -> Read<ArrayBuffer> {
+  read(blob, FileReader::read_as_array_buffer, JsCast::unchecked_into)
+}
+
+/// Reads the contents of `blob` as UTF-8 text.
+pub fn read_as_text(blob: &Blob) -> Read<String> {
+  read(blob, FileReader::read_as_text, |result| result.as_string().unwrap_or_default())
+}
+
+/// Reads the contents of `blob` as a `data:` URL, encoded as base64.
+pub fn read_as_data_url(blob: &Blob) -> Read<String> {
+  read(blob, FileReader::read_as_data_url, |result| result.as_string().unwrap_or_default())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{read_as_array_buffer, read_as_data_url, read_as_text, read_with};
+  use js_sys::Array;
+  use wasm_bindgen_test::*;
+  use web_sys::{Blob, FileReader};
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  fn blob(text: &str) -> Blob {
+    Blob::new_with_str_sequence(&Array::of1(&text.into())).unwrap()
+  }
+
+  #[wasm_bindgen_test]
+  async fn reads_text() {
+    assert_eq!(read_as_text(&blob("hello")).await.unwrap(), "hello");
+  }
+
+  #[wasm_bindgen_test]
+  async fn reads_array_buffer() {
+    let buffer = read_as_array_buffer(&blob("hello")).await.unwrap();
+    assert_eq!(buffer.byte_length(), 5);
+  }
+
+  #[wasm_bindgen_test]
+  async fn reads_data_url() {
+    let url = read_as_data_url(&blob("hello")).await.unwrap();
+    assert!(url.starts_with("data:"));
+    assert!(url.ends_with("aGVsbG8="));
+  }
+
+  #[wasm_bindgen_test]
+  fn dropping_aborts_the_read() {
+    let reader = FileReader::new().unwrap();
+    drop(read_with(&reader, &blob("hello"), FileReader::read_as_text, |result| result));
+    assert_eq!(reader.ready_state(), FileReader::DONE);
+    assert!(reader.result().unwrap().is_null()); // Assert no result, since the read was aborted
+  }
+
+  #[wasm_bindgen_test]
+  async fn failing_to_start_rejects() {
+    let reader = FileReader::new().unwrap();
+    let first = read_with(&reader, &blob("hello"), FileReader::read_as_text, |result| result);
+    // A reader which is already loading throws an `InvalidStateError` when started again.
+    let second = read_with(&reader, &blob("again"), FileReader::read_as_text, |result| result);
+    assert!(second.await.is_err()); // Assert is `Err`
+    assert_eq!(first.await.unwrap(), "hello");
+  }
+}
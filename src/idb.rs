@@ -0,0 +1,150 @@
+use crate::{CallbackPair, EventFuture};
+use core::cell::RefCell;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use web_sys::{IdbOpenDbRequest, IdbRequest, IdbVersionChangeEvent};
+
+/// The `DomException` a failed request rejects with, falling back to the error event itself.
+fn request_error(request: &IdbRequest, event: JsValue) -> JsValue {
+  match request.error() {
+    Ok(Some(err)) => err.into(),
+    _ => event,
+  }
+}
+
+impl CallbackPair<dyn FnMut(JsValue), dyn FnMut(JsValue)> {
+  /// Creates a callback pair from the `onsuccess` and `onerror` handlers of `request`, which resolves with its
+  /// `result`, or rejects with its `error`. Both handlers are cleared as soon as either is called, or when the
+  /// future is dropped.
+  pub fn from_idb_request(request: &IdbRequest) -> EventFuture<Self> {
+    let (ok, err) = (request.clone(), request.clone());
+    EventFuture::pair_with(
+      move |_event| ok.result(),
+      move |event| Err(request_error(&err, event)),
+      |resolve, reject| {
+        request.set_onsuccess(Some(resolve));
+        request.set_onerror(Some(reject));
+        let request = request.clone();
+        move || {
+          request.set_onsuccess(None);
+          request.set_onerror(None);
+        }
+      },
+    )
+  }
+
+  /// Like `from_idb_request`, for opening a database.
+  ///
+  /// `on_upgrade` is called from `upgradeneeded`, while the version change transaction is still active. If it
+  /// returns Err, the transaction is aborted and the future rejects with that error. `on_blocked` is called for
+  /// each `blocked` event, while other connections keep the database from being upgraded, and the future keeps
+  /// waiting as the request itself does.
+  pub fn from_idb_open_request<U, B>(request: &IdbOpenDbRequest, on_upgrade: U, on_blocked: B) -> EventFuture<Self>
+  where
+    U: 'static + FnOnce(IdbVersionChangeEvent) -> Result<(), JsValue>,
+    B: 'static + FnMut(IdbVersionChangeEvent),
+  {
+    let upgrade_err: Rc<RefCell<Option<JsValue>>> = Rc::default();
+    let upgrade = {
+      let (request, upgrade_err) = (request.clone(), Rc::clone(&upgrade_err));
+      Closure::once(move |event: IdbVersionChangeEvent| {
+        if let Err(err) = on_upgrade(event) {
+          upgrade_err.replace(Some(err));
+          if let Some(transaction) = request.transaction() {
+            let _ = transaction.abort();
+          }
+        }
+      })
+    };
+    let blocked: Closure<dyn FnMut(IdbVersionChangeEvent)> = Closure::wrap(Box::new(on_blocked));
+    let (ok, err) = (request.clone(), request.clone());
+    EventFuture::pair_with(
+      move |_event| ok.result(),
+      move |event| Err(upgrade_err.take().unwrap_or_else(|| request_error(&err, event))),
+      |resolve, reject| {
+        request.set_onsuccess(Some(resolve));
+        request.set_onerror(Some(reject));
+        request.set_onupgradeneeded(Some(upgrade.as_ref().unchecked_ref()));
+        request.set_onblocked(Some(blocked.as_ref().unchecked_ref()));
+        let request = request.clone();
+        move || {
+          request.set_onsuccess(None);
+          request.set_onerror(None);
+          request.set_onupgradeneeded(None);
+          request.set_onblocked(None);
+          drop((upgrade, blocked));
+        }
+      },
+    )
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::CallbackPair;
+  use wasm_bindgen::JsCast;
+  use wasm_bindgen_test::*;
+  use web_sys::{window, DomException, IdbDatabase, IdbFactory};
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  fn idb() -> IdbFactory {
+    window()
+      .expect("window not available")
+      .indexed_db()
+      .unwrap()
+      .expect("idb not available")
+  }
+
+  #[wasm_bindgen_test]
+  async fn resolves_with_result() {
+    let req = idb().open("from_idb_request").expect("Failed to get idb request");
+    let result = CallbackPair::from_idb_request(&req).await;
+    let db: IdbDatabase = result.unwrap().dyn_into().unwrap();
+    assert_eq!(db.name(), "from_idb_request");
+    db.close();
+    assert!(req.onsuccess().is_none()); // Assert handlers cleared
+    assert!(req.onerror().is_none());
+  }
+
+  #[wasm_bindgen_test]
+  async fn open_calls_upgrade() {
+    let _ = idb().delete_database("from_idb_open_request");
+    let req = idb()
+      .open_with_u32("from_idb_open_request", 2)
+      .expect("Failed to get idb request");
+    let future = CallbackPair::from_idb_open_request(
+      &req,
+      |event| {
+        assert_eq!(event.new_version(), Some(2.0));
+        Ok(())
+      },
+      |_event| (),
+    );
+    let db: IdbDatabase = future.await.unwrap().dyn_into().unwrap();
+    assert_eq!(db.version(), 2.0);
+    db.close();
+    assert!(req.onupgradeneeded().is_none()); // Assert handlers cleared
+  }
+
+  #[wasm_bindgen_test]
+  async fn open_rejects_with_upgrade_err() {
+    let _ = idb().delete_database("from_idb_open_request_err");
+    let req = idb()
+      .open("from_idb_open_request_err")
+      .expect("Failed to get idb request");
+    let future = CallbackPair::from_idb_open_request(&req, |_event| Err("upgrade failed".into()), |_event| ());
+    assert_eq!(future.await.unwrap_err(), "upgrade failed");
+  }
+
+  #[wasm_bindgen_test]
+  async fn rejects_with_dom_exception() {
+    let req = idb().open_with_u32("from_idb_request_version", 2).unwrap();
+    let db: IdbDatabase = CallbackPair::from_idb_request(&req).await.unwrap().dyn_into().unwrap();
+    db.close();
+    let req = idb().open_with_u32("from_idb_request_version", 1).unwrap(); // Lower than the existing version
+    let err = CallbackPair::from_idb_request(&req).await.unwrap_err();
+    assert_eq!(err.dyn_into::<DomException>().unwrap().name(), "VersionError");
+  }
+}
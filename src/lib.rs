@@ -1,8 +1,41 @@
+mod backend;
 mod callback;
 mod callback_pair;
+mod callback_stream;
+mod callbackify;
+mod collect_args;
+pub mod convention;
+mod deferred;
+#[cfg(feature = "serde")]
+mod deserialize;
+mod dual;
+mod event;
+pub mod file_reader;
+mod idb;
+#[cfg(feature = "node")]
+pub mod node;
+mod promisify;
+mod returning;
+pub mod timers;
+mod typed;
 
+pub use backend::{
+  Backend, FakeBackend, FakeFunction, Handler, HandlerMut, HandlerReturn, ReturnSignature, Signature, WasmBackend,
+};
 pub use callback::Callback;
 pub use callback_pair::CallbackPair;
+pub use callback_stream::CallbackStream;
+pub use callbackify::callbackify;
+pub use collect_args::{Args, Arity};
+pub use convention::ErrorConvention;
+pub use deferred::{Deferred, Resolver};
+#[cfg(feature = "serde")]
+pub use deserialize::{Deserialize, DeserializeError};
+pub use dual::{call_dual, DualCall};
+pub use event::{EventFuture, EventStream};
+pub use promisify::{NotThrown, Promisified, Returning, Thrown};
+pub use returning::{ReturnHandler, Returned};
+pub use typed::{cast_arg, FromArg, FromCallbackArgs, Typed};
 
 #[cfg(test)]
 mod tests {
@@ -0,0 +1,153 @@
+//! Helpers for node-style javascript APIs, enabled with the `node` feature.
+use crate::callbackify::variadic;
+use crate::{Callback, CallbackPair, EventFuture};
+use core::cell::RefCell;
+use js_sys::{Array, Function, Object, Promise, Proxy, Reflect, Symbol};
+use std::collections::HashMap;
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+use wasm_bindgen_futures::{future_to_promise, JsFuture};
+
+#[wasm_bindgen]
+extern "C" {
+  /// Any object implementing node's `EventEmitter` interface, such as `process` or a stream.
+  #[wasm_bindgen(extends = Object)]
+  #[derive(Debug, Clone)]
+  pub type EventEmitter;
+
+  #[wasm_bindgen(method)]
+  fn once(this: &EventEmitter, event: &str, listener: &Function) -> EventEmitter;
+
+  #[wasm_bindgen(method, js_name = removeListener)]
+  fn remove_listener(this: &EventEmitter, event: &str, listener: &Function) -> EventEmitter;
+}
+
+/// The future returned by [`once`].
+pub type Once = EventFuture<CallbackPair<dyn FnMut(JsValue), dyn FnMut(JsValue)>>;
+
+/// Waits for `emitter` to emit `event`, resolving with the first argument it is emitted with, or rejecting with
+/// the first `error` emitted, similar to node's `events.once`. Both listeners are removed once either fires, or
+/// when the future is dropped.
+pub fn once(emitter: &EventEmitter, event: &str) -> Once {
+  EventFuture::pair(|resolve, reject| {
+    emitter.once(event, resolve);
+    emitter.once("error", reject);
+    let (emitter, event) = (emitter.clone(), event.to_owned());
+    let (resolve, reject) = (resolve.clone(), reject.clone());
+    move || {
+      emitter.remove_listener(&event, &resolve);
+      emitter.remove_listener("error", &reject);
+    }
+  })
+}
+
+/// Returns the variant of `func` which `util.promisify` would use instead of wrapping it, if `func` has one under
+/// `Symbol.for('nodejs.util.promisify.custom')`, such as `setTimeout` or `fs.exists`.
+fn custom_promisified(func: &Function) -> Option<Function> {
+  let symbol = Symbol::for_("nodejs.util.promisify.custom");
+  Reflect::get(func, &symbol).ok()?.dyn_into().ok()
+}
+
+/// Calls `func` with `args` and a node-style callback appended, and waits for the callback with
+/// `Callback::default_node`. If `func` throws instead, the error is returned immediately.
+///
+/// Like `util.promisify`, if `func` has a custom promisified variant, that is called with `args` instead, and its
+/// promise awaited.
+pub async fn call(this: &JsValue, func: &Function, args: &Array) -> Result<JsValue, JsValue> {
+  if let Some(custom) = custom_promisified(func) {
+    let promise = Promise::resolve(&custom.apply(this, args)?);
+    return JsFuture::from(promise).await;
+  }
+  let future = Callback::default_node();
+  let args = args.concat(&Array::of1(&future.as_function()));
+  func.apply(this, &args)?;
+  future.await
+}
+
+/// Like [`call`], for the method `name` of `obj`, such as `fs.readFile` or `child_process.exec`.
+pub async fn call_method(obj: &Object, name: &str, args: &Array) -> Result<JsValue, JsValue> {
+  let func: Function = Reflect::get(obj, &name.into())?
+    .dyn_into()
+    .map_err(|_| js_sys::TypeError::new(&format!("{} is not a function", name)))?;
+  call(obj, &func, args).await
+}
+
+/// Whether `key` is defined by `object` or a prototype below `Object.prototype`, other than as the `constructor`
+/// of a class.
+fn is_method(object: &Object, key: &JsValue) -> bool {
+  if key == "constructor" {
+    return false;
+  }
+  let base = Object::get_prototype_of(&Object::new());
+  let mut object = object.clone();
+  while !object.is_null() && !Object::is(&object, &base) {
+    if Object::has_own(&object, key) {
+      return true;
+    }
+    object = Object::get_prototype_of(&object);
+  }
+  false
+}
+
+/// A handle to an object whose methods take node-style callbacks, created with [`promisify_all`].
+#[derive(Debug, Clone)]
+pub struct PromisifiedObject {
+  object: Object,
+  methods: Rc<RefCell<HashMap<String, Function>>>,
+}
+
+/// Wraps the methods of `object`, such as node's `fs` module or a legacy SDK client, to return promises instead of
+/// taking a callback. Each method is wrapped on first use, and the wrapper is cached.
+pub fn promisify_all(object: &Object) -> PromisifiedObject {
+  PromisifiedObject {
+    object: object.clone(),
+    methods: Rc::default(),
+  }
+}
+
+impl PromisifiedObject {
+  /// Calls the method `name` with `args`, and waits for its node-style callback, as with [`call_method`].
+  pub async fn call_async(&self, name: &str, args: &Array) -> Result<JsValue, JsValue> {
+    let promise: Promise = self.method(name)?.apply(&JsValue::NULL, args)?.unchecked_into();
+    JsFuture::from(promise).await
+  }
+
+  /// Returns the wrapper of the method `name`, a javascript function taking the same arguments except for the
+  /// callback, and returning a promise. The wrapper is never deallocated.
+  pub fn method(&self, name: &str) -> Result<Function, JsValue> {
+    if let Some(method) = self.methods.borrow().get(name) {
+      return Ok(method.clone());
+    }
+    let func: Function = Reflect::get(&self.object, &name.into())?
+      .dyn_into()
+      .map_err(|_| js_sys::TypeError::new(&format!("{} is not a function", name)))?;
+    let object = self.object.clone();
+    let closure: Closure<dyn FnMut(Array) -> Promise> = Closure::wrap(Box::new(move |args: Array| {
+      let (object, func) = (object.clone(), func.clone());
+      future_to_promise(async move { call(&object, &func, &args).await })
+    }));
+    let method = variadic(closure.into_js_value().unchecked_ref());
+    self.methods.borrow_mut().insert(name.to_owned(), method.clone());
+    Ok(method)
+  }
+
+  /// Creates a `Proxy` of the object for javascript, whose methods are replaced with their wrappers, sharing this
+  /// handle's cache. This includes methods inherited from a class, but not those of `Object.prototype` such as
+  /// `toString`, nor `constructor`, which are passed through with any other property, and can still be wrapped
+  /// with [`PromisifiedObject::method`]. The proxy is never deallocated.
+  pub fn as_proxy(&self) -> Proxy {
+    let this = self.clone();
+    let get: Closure<dyn FnMut(Object, JsValue) -> Result<JsValue, JsValue>> =
+      Closure::wrap(Box::new(move |target: Object, key: JsValue| {
+        let value = Reflect::get(&target, &key)?;
+        match key.as_string() {
+          Some(name) if value.is_function() && is_method(&target, &key) => this.method(&name).map(JsValue::from),
+          _ => Ok(value),
+        }
+      }));
+    let handler = Object::new();
+    Reflect::set(&handler, &"get".into(), &get.into_js_value()).unwrap_throw();
+    Proxy::new(&self.object, &handler)
+  }
+}
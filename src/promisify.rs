@@ -0,0 +1,148 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+use wasm_bindgen::JsValue;
+
+/// Calls a function or method with a callback in place of the `_` placeholder, and evaluates to a
+/// [`Promisified`] future of the callback's result.
+///
+/// A single `_` creates a `Callback::default()`, or a `Callback::default_node()` if the call is prefixed with
+/// `node;`. Placing both `_ok` and `_err` instead creates a `CallbackPair::default()`. Placeholders are only
+/// replaced where they are a whole argument. If the call returns a `Result` with a `JsValue` error, such as a
+/// `catch` binding, an Err is returned by the future immediately, and anything else it returns is discarded.
+///
+/// ```ignore
+/// let timeout = promisify!(window.set_timeout_with_callback_and_timeout_and_arguments_0(_, 500)).await;
+/// let contents = promisify!(node; fs.read_file("data.txt", _)).await;
+/// let image = promisify!(load_image(url, _ok, _err)).await;
+/// ```
+#[macro_export]
+macro_rules! promisify {
+  // Collects the function being called, up to its final, parenthesized argument list.
+  (@callee $ctx:tt [$($callee:tt)*] ($($args:tt)*)) => {
+    $crate::promisify!(@args $ctx [$($callee)*] [] [] $($args)*)
+  };
+  (@callee $ctx:tt [$($callee:tt)*] $next:tt $($rest:tt)+) => {
+    $crate::promisify!(@callee $ctx [$($callee)* $next] $($rest)+)
+  };
+  // At the start of an argument, replaces a placeholder making up the whole argument with its function, and
+  // records which were found.
+  (@args ($cb:ident $ok:ident $err:ident $conv:ident) $callee:tt [$($args:tt)*] [$($found:tt)*] _ $(, $($rest:tt)*)?) => {
+    $crate::promisify!(@args ($cb $ok $err $conv) $callee [$($args)* &$cb,] [$($found)* _] $($($rest)*)?)
+  };
+  (@args ($cb:ident $ok:ident $err:ident $conv:ident) $callee:tt [$($args:tt)*] [$($found:tt)*] _ok $(, $($rest:tt)*)?) => {
+    $crate::promisify!(@args ($cb $ok $err $conv) $callee [$($args)* &$ok,] [$($found)* _ok] $($($rest)*)?)
+  };
+  (@args ($cb:ident $ok:ident $err:ident $conv:ident) $callee:tt [$($args:tt)*] [$($found:tt)*] _err $(, $($rest:tt)*)?) => {
+    $crate::promisify!(@args ($cb $ok $err $conv) $callee [$($args)* &$err,] [$($found)* _err] $($($rest)*)?)
+  };
+  (@args $ctx:tt $callee:tt $args:tt $found:tt) => {
+    $crate::promisify!(@finish $ctx $callee $args $found)
+  };
+  (@args $ctx:tt $callee:tt $args:tt $found:tt $($rest:tt)+) => {
+    $crate::promisify!(@arg $ctx $callee $args $found $($rest)+)
+  };
+  // Copies any other argument up to the next comma.
+  (@arg $ctx:tt $callee:tt [$($args:tt)*] $found:tt , $($rest:tt)*) => {
+    $crate::promisify!(@args $ctx $callee [$($args)* ,] $found $($rest)*)
+  };
+  (@arg $ctx:tt $callee:tt [$($args:tt)*] $found:tt $next:tt $($rest:tt)*) => {
+    $crate::promisify!(@arg $ctx $callee [$($args)* $next] $found $($rest)*)
+  };
+  (@arg $ctx:tt $callee:tt $args:tt $found:tt) => {
+    $crate::promisify!(@finish $ctx $callee $args $found)
+  };
+  // Creates the callback for the placeholders found, and makes the call.
+  (@finish ($cb:ident $ok:ident $err:ident $conv:ident) [$($callee:tt)*] [$($args:tt)*] [_]) => {{
+    let future = $crate::promisify!(@convention $conv);
+    let $cb = future.as_function();
+    let thrown = $crate::promisify!(@thrown $($callee)*($($args)*));
+    $crate::Promisified::new(future, thrown)
+  }};
+  (@finish ($cb:ident $ok:ident $err:ident default) [$($callee:tt)*] [$($args:tt)*] [$_a:tt $_b:tt]) => {{
+    let future = $crate::CallbackPair::default();
+    let ($ok, $err) = future.as_functions();
+    let thrown = $crate::promisify!(@pair [$_a $_b] $($callee)*($($args)*));
+    $crate::Promisified::new(future, thrown)
+  }};
+  (@finish $ctx:tt $callee:tt $args:tt $found:tt) => {
+    compile_error!("promisify! expects either a single `_`, or both `_ok` and `_err`, and `node;` only with `_`")
+  };
+  // Only accepts `_ok` and `_err` as a pair, in either order.
+  (@pair [_ok _err] $($call:tt)*) => { $crate::promisify!(@thrown $($call)*) };
+  (@pair [_err _ok] $($call:tt)*) => { $crate::promisify!(@thrown $($call)*) };
+  (@pair $found:tt $($call:tt)*) => {
+    compile_error!("promisify! expects either a single `_`, or both `_ok` and `_err`")
+  };
+  // Takes the error out of whatever the call returned, if it is a `Result<_, JsValue>`.
+  (@thrown $($call:tt)*) => {{
+    #[allow(unused_imports)]
+    use $crate::{NotThrown as _, Thrown as _};
+    (&$crate::Returning($($call)*)).thrown()
+  }};
+  (@convention default) => { $crate::Callback::default() };
+  (@convention node) => { $crate::Callback::default_node() };
+  (node; $($call:tt)+) => {
+    $crate::promisify!(@callee (cb ok err node) [] $($call)+)
+  };
+  ($($call:tt)+) => {
+    $crate::promisify!(@callee (cb ok err default) [] $($call)+)
+  };
+}
+
+/// The future [`promisify!`] evaluates to, which resolves with the Result of its callback, or rejects immediately
+/// with the error the call itself returned.
+#[derive(Debug)]
+pub struct Promisified<C> {
+  future: C,
+  thrown: Option<JsValue>,
+}
+
+impl<C> Promisified<C> {
+  #[doc(hidden)]
+  pub fn new(future: C, thrown: Option<JsValue>) -> Promisified<C> {
+    Promisified { future, thrown }
+  }
+}
+
+impl<C> Future for Promisified<C>
+where
+  C: Future<Output = Result<JsValue, JsValue>> + Unpin,
+{
+  type Output = Result<JsValue, JsValue>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+    let this = self.get_mut();
+    if let Some(err) = this.thrown.take() {
+      return Poll::Ready(Err(err));
+    }
+    Pin::new(&mut this.future).poll(cx)
+  }
+}
+
+/// Whatever the call made by `promisify!` returned. Method resolution picks [`Thrown`] for a `Result<_, JsValue>`,
+/// and falls back to [`NotThrown`] through an extra reference for anything else.
+#[doc(hidden)]
+pub struct Returning<T>(pub T);
+
+#[doc(hidden)]
+pub trait Thrown {
+  fn thrown(&self) -> Option<JsValue>;
+}
+
+impl<T> Thrown for Returning<Result<T, JsValue>> {
+  fn thrown(&self) -> Option<JsValue> {
+    self.0.as_ref().err().cloned()
+  }
+}
+
+#[doc(hidden)]
+pub trait NotThrown {
+  fn thrown(&self) -> Option<JsValue>;
+}
+
+impl<T> NotThrown for &Returning<T> {
+  fn thrown(&self) -> Option<JsValue> {
+    None
+  }
+}
@@ -0,0 +1,106 @@
+use crate::backend::{self, Backend, ReturnSignature, WasmBackend};
+use crate::Callback;
+use wasm_bindgen::JsValue;
+
+/// What a handler passed to [`Callback::with_return`] returns: the value for the javascript caller, and the
+/// Result to finish the callback with, if it should finish.
+pub type Returned = (JsValue, Option<Result<JsValue, JsValue>>);
+
+/// Implemented for every closure a callback may be created with by [`Callback::with_return`], taking up to 7
+/// `JsValue` args and returning [`Returned`]. `A` is the tuple of its argument types.
+pub trait ReturnHandler<A>: 'static {
+  /// The `dyn FnMut(JsValue, ..) -> JsValue` of the function handed to javascript.
+  type Signature: 'static + ?Sized + ReturnSignature;
+
+  /// Erases the arity of the handler, padding or truncating the arguments the way javascript would.
+  fn into_handler(self) -> Box<dyn FnMut(Vec<JsValue>) -> Returned>;
+}
+
+/// A utility macro for generating an implementation of `ReturnHandler` for every argument list.
+macro_rules! return_handler_impl {
+  ($($alist:ident)*) => {
+    impl<X> ReturnHandler<($(return_handler_impl!(@rep $alist JsValue),)*)> for X
+    where
+      X: 'static + FnMut($(return_handler_impl!(@rep $alist JsValue)),*) -> Returned,
+    {
+      type Signature = dyn FnMut($(return_handler_impl!(@rep $alist JsValue)),*) -> JsValue;
+
+      fn into_handler(mut self) -> Box<dyn FnMut(Vec<JsValue>) -> Returned> {
+        Box::new(move |args| {
+          let [$($alist),*] = backend::args(args);
+          self($($alist),*)
+        })
+      }
+    }
+  };
+  // Utility for replacing anything with a type.
+  (@rep $_t:tt $sub:ty) => {
+    $sub
+  };
+}
+
+return_handler_impl!();
+return_handler_impl!(a0);
+return_handler_impl!(a0 a1);
+return_handler_impl!(a0 a1 a2);
+return_handler_impl!(a0 a1 a2 a3);
+return_handler_impl!(a0 a1 a2 a3 a4);
+return_handler_impl!(a0 a1 a2 a3 a4 a5);
+return_handler_impl!(a0 a1 a2 a3 a4 a5 a6);
+
+impl Callback<dyn FnMut(JsValue)> {
+  /// Creates a callback whose function returns a value to its javascript caller, such as a sort comparator or a
+  /// `beforeunload` handler. Each call of `handler` produces the value to return, and optionally the Result to
+  /// finish the callback with.
+  ///
+  /// Until it finishes, the function may be called any number of times. Once it does, it is deallocated after
+  /// returning, so it must not be called again.
+  pub fn with_return<X, A>(handler: X) -> Callback<X::Signature>
+  where
+    X: ReturnHandler<A>,
+  {
+    Self::with_return_in(handler, WasmBackend)
+  }
+
+  /// Like `with_return`, but for a backend other than the default.
+  pub fn with_return_in<X, A, H>(handler: X, _backend: H) -> Callback<X::Signature, H>
+  where
+    X: ReturnHandler<A>,
+    H: Backend,
+  {
+    Callback::from_return(handler.into_handler())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use crate::Callback;
+  use wasm_bindgen::prelude::*;
+  use wasm_bindgen_test::*;
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  #[wasm_bindgen(inline_js = "export function exclaim(cb) { return cb('hello') + '!'; };
+    export function sum_of_calls(cb) { return cb(1) + cb(2); };")]
+  extern "C" {
+    fn exclaim(cb: &js_sys::Function) -> String;
+    fn sum_of_calls(cb: &js_sys::Function) -> f64;
+  }
+
+  #[wasm_bindgen_test]
+  async fn returns_to_caller_and_resolves() {
+    let future = Callback::with_return(|greeting: JsValue| (greeting.clone(), Some(Ok(greeting))));
+    assert_eq!(exclaim(&future.as_function()), "hello!");
+    assert_eq!(future.await.unwrap(), "hello");
+  }
+
+  #[wasm_bindgen_test]
+  async fn called_until_finished() {
+    let future = Callback::with_return(|n: JsValue| {
+      let last = n == 2;
+      (n.clone(), Some(Ok(n)).filter(|_| last))
+    });
+    assert_eq!(sum_of_calls(&future.as_function()), 3.0);
+    assert_eq!(future.await.unwrap(), 2);
+  }
+}
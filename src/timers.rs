@@ -0,0 +1,418 @@
+//! Timer primitives built on `Callback`, which work in both browser and node global scopes, except for animation
+//! frames, which only exist in browsers.
+use crate::{Callback, CallbackStream};
+use futures_core::Stream;
+use js_sys::Function;
+use std::future::Future;
+use std::pin::Pin;
+use std::task::Poll;
+use std::time::Duration;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+#[wasm_bindgen]
+extern "C" {
+  // Timer ids are numbers in browsers, but `Timeout` objects in node.
+  #[wasm_bindgen(js_name = setTimeout)]
+  fn set_timeout(handler: &Function, timeout: i32) -> JsValue;
+
+  #[wasm_bindgen(js_name = clearTimeout)]
+  fn clear_timeout(id: &JsValue);
+
+  #[wasm_bindgen(js_name = setInterval)]
+  fn set_interval(handler: &Function, timeout: i32) -> JsValue;
+
+  #[wasm_bindgen(js_name = clearInterval)]
+  fn clear_interval(id: &JsValue);
+
+  #[wasm_bindgen(js_name = requestAnimationFrame)]
+  fn request_animation_frame(handler: &Function) -> i32;
+
+  #[wasm_bindgen(js_name = cancelAnimationFrame)]
+  fn cancel_animation_frame(id: i32);
+}
+
+#[wasm_bindgen(inline_js = "export function request_idle(cb, timeout) {
+    if (typeof requestIdleCallback === 'function') {
+      return { idle: requestIdleCallback(cb, timeout === undefined ? undefined : { timeout }) };
+    }
+    return { timer: setTimeout(() => {
+      const start = Date.now();
+      cb({ didTimeout: false, timeRemaining: () => Math.max(0, 50 - (Date.now() - start)) });
+    }, 1) };
+  };
+  export function cancel_idle(handle) {
+    handle.idle !== undefined ? cancelIdleCallback(handle.idle) : clearTimeout(handle.timer);
+  };")]
+extern "C" {
+  /// Falls back to a `setTimeout` shim with a 50ms deadline where `requestIdleCallback` is missing.
+  fn request_idle(handler: &Function, timeout: Option<i32>) -> JsValue;
+
+  fn cancel_idle(handle: &JsValue);
+}
+
+#[wasm_bindgen(inline_js = "let queue = [], channel;
+  export function macrotask(cb) {
+    if (typeof setImmediate === 'function') {
+      setImmediate(cb);
+    } else if (typeof MessageChannel === 'function') {
+      if (channel === undefined) {
+        channel = new MessageChannel();
+        channel.port1.onmessage = () => queue.shift()();
+      }
+      queue.push(cb);
+      channel.port2.postMessage(undefined);
+    } else {
+      setTimeout(cb, 0);
+    }
+  };")]
+extern "C" {
+  /// Queues a macrotask without the clamping `setTimeout` is subject to.
+  fn macrotask(handler: &Function);
+}
+
+#[wasm_bindgen]
+extern "C" {
+  /// The `IdleDeadline` an idle callback is called with, or the shim's stand-in for it.
+  #[wasm_bindgen(extends = js_sys::Object)]
+  #[derive(Debug, Clone)]
+  pub type IdleDeadline;
+
+  /// The estimated milliseconds left in the idle period.
+  #[wasm_bindgen(method, js_name = timeRemaining)]
+  pub fn time_remaining(this: &IdleDeadline) -> f64;
+
+  /// Whether the callback was called because its timeout elapsed, rather than the host becoming idle.
+  #[wasm_bindgen(method, getter, js_name = didTimeout)]
+  pub fn did_timeout(this: &IdleDeadline) -> bool;
+}
+
+/// Timers overflow to firing immediately past `i32::MAX` milliseconds, so clamp to it instead.
+fn millis(duration: Duration) -> i32 {
+  duration.as_millis().min(i32::MAX as u128) as i32
+}
+
+/// A future which resolves once its duration has elapsed. The timeout is cleared if it is dropped before then.
+pub struct Sleep {
+  future: Callback<dyn FnMut()>,
+  id: JsValue,
+}
+
+impl Future for Sleep {
+  type Output = ();
+
+  fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+    Pin::new(&mut self.get_mut().future).poll(cx).map(|_| ())
+  }
+}
+
+impl Drop for Sleep {
+  fn drop(&mut self) {
+    clear_timeout(&self.id);
+    self.future.release();
+  }
+}
+
+/// Waits for `duration` with `setTimeout`. See [`Sleep`].
+pub fn sleep(duration: Duration) -> Sleep {
+  let future = Callback::new(|| Ok(JsValue::UNDEFINED));
+  let id = set_timeout(&future.as_function(), millis(duration));
+  Sleep { future, id }
+}
+
+/// A stream which yields every time its period elapses. The interval is cleared when it is dropped.
+pub struct Interval {
+  stream: CallbackStream<dyn FnMut()>,
+  id: JsValue,
+}
+
+impl Stream for Interval {
+  type Item = ();
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<Self::Item>> {
+    match Pin::new(&mut self.get_mut().stream).poll_next(cx) {
+      Poll::Ready(Some(_)) => Poll::Ready(Some(())),
+      Poll::Ready(None) => Poll::Ready(None),
+      Poll::Pending => Poll::Pending,
+    }
+  }
+}
+
+impl Drop for Interval {
+  fn drop(&mut self) {
+    clear_interval(&self.id);
+  }
+}
+
+/// Yields every `period` with `setInterval`. See [`Interval`].
+pub fn interval(period: Duration) -> Interval {
+  let stream = CallbackStream::new(|| Ok(JsValue::UNDEFINED));
+  let id = set_interval(&stream.as_function(), millis(period));
+  Interval { stream, id }
+}
+
+/// A future which resolves with the `DOMHighResTimeStamp` of the next animation frame. The request is cancelled if
+/// it is dropped before then.
+pub struct NextAnimationFrame {
+  future: Callback<dyn FnMut(JsValue)>,
+  id: i32,
+}
+
+impl Future for NextAnimationFrame {
+  type Output = f64;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+    Pin::new(&mut self.get_mut().future)
+      .poll(cx)
+      .map(|time| time.ok().and_then(|time| time.as_f64()).unwrap_or_default())
+  }
+}
+
+impl Drop for NextAnimationFrame {
+  fn drop(&mut self) {
+    cancel_animation_frame(self.id);
+    self.future.release();
+  }
+}
+
+/// Waits for the next frame with `requestAnimationFrame`. See [`NextAnimationFrame`].
+pub fn next_animation_frame() -> NextAnimationFrame {
+  let future = Callback::default();
+  let id = request_animation_frame(&future.as_function());
+  NextAnimationFrame { future, id }
+}
+
+/// A stream which yields the `DOMHighResTimeStamp` of every animation frame. The next frame is only requested once
+/// the previous one has been taken, so a slow consumer skips frames rather than queueing them. The outstanding
+/// request is cancelled when it is dropped.
+pub struct AnimationFrames {
+  stream: CallbackStream<dyn FnMut(JsValue)>,
+  listener: Function,
+  id: Option<i32>,
+}
+
+impl Stream for AnimationFrames {
+  type Item = f64;
+
+  fn poll_next(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Option<Self::Item>> {
+    let this = self.get_mut();
+    if this.id.is_none() {
+      this.id = Some(request_animation_frame(&this.listener));
+    }
+    match Pin::new(&mut this.stream).poll_next(cx) {
+      Poll::Ready(Some(time)) => {
+        this.id = None;
+        Poll::Ready(Some(time.ok().and_then(|time| time.as_f64()).unwrap_or_default()))
+      }
+      Poll::Ready(None) => Poll::Ready(None),
+      Poll::Pending => Poll::Pending,
+    }
+  }
+}
+
+impl Drop for AnimationFrames {
+  fn drop(&mut self) {
+    if let Some(id) = self.id.take() {
+      cancel_animation_frame(id);
+    }
+  }
+}
+
+/// Yields every animation frame, re-registering with `requestAnimationFrame` each time. See [`AnimationFrames`].
+pub fn animation_frames() -> AnimationFrames {
+  let stream = CallbackStream::default();
+  let listener = stream.as_function();
+  AnimationFrames {
+    stream,
+    listener,
+    id: None,
+  }
+}
+
+/// A future which resolves with the [`IdleDeadline`] of the next idle period. The request is cancelled if it is
+/// dropped before then.
+pub struct Idle {
+  future: Callback<dyn FnMut(JsValue)>,
+  handle: JsValue,
+}
+
+impl Future for Idle {
+  type Output = IdleDeadline;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+    Pin::new(&mut self.get_mut().future)
+      .poll(cx)
+      .map(|deadline| deadline.unwrap_or_else(|err| err).unchecked_into())
+  }
+}
+
+impl Drop for Idle {
+  fn drop(&mut self) {
+    cancel_idle(&self.handle);
+    self.future.release();
+  }
+}
+
+/// Waits for the host to become idle with `requestIdleCallback`. See [`Idle`].
+pub fn idle() -> Idle {
+  request(None)
+}
+
+/// Like [`idle`], but resolves once `timeout` has elapsed even if the host never became idle.
+pub fn idle_with_timeout(timeout: Duration) -> Idle {
+  request(Some(millis(timeout)))
+}
+
+fn request(timeout: Option<i32>) -> Idle {
+  let future = Callback::default();
+  let handle = request_idle(&future.as_function(), timeout);
+  Idle { future, handle }
+}
+
+/// Yields to the host event loop, letting it handle any pending events before continuing. This uses a
+/// `MessageChannel`, or `setImmediate` in node, rather than `setTimeout(0)`, which may be clamped to 4ms or more.
+pub async fn yield_now() {
+  let future = Callback::new(|| Ok(JsValue::UNDEFINED));
+  macrotask(&future.as_function());
+  let _ = future.await;
+}
+
+#[cfg(test)]
+mod tests {
+  use super::{animation_frames, idle, idle_with_timeout, interval, next_animation_frame, sleep, yield_now};
+  use crate::callback_stream::next;
+  use futures_core::Stream;
+  use js_sys::Date;
+  use std::pin::Pin;
+  use std::task::{Context, Waker};
+  use std::time::Duration;
+  use wasm_bindgen::prelude::*;
+  use wasm_bindgen_test::*;
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  #[wasm_bindgen(inline_js = "export function spy(name) {
+      const original = globalThis[name];
+      const spy = { calls: 0, restore: () => { globalThis[name] = original; } };
+      globalThis[name] = (cb, ...rest) => original((...args) => { spy.calls++; cb(...args); }, ...rest);
+      return spy;
+    };
+    export function idle_scheduler() {
+      return typeof requestIdleCallback === 'function' ? 'requestIdleCallback' : 'setTimeout';
+    };")]
+  extern "C" {
+    /// Counts the calls of every handler scheduled with the global function `name` until restored.
+    type Spy;
+    fn spy(name: &str) -> Spy;
+    #[wasm_bindgen(method, getter)]
+    fn calls(this: &Spy) -> u32;
+    #[wasm_bindgen(method)]
+    fn restore(this: &Spy);
+    /// The global function `idle` schedules with, depending on whether the shim is in use.
+    fn idle_scheduler() -> String;
+  }
+
+  #[wasm_bindgen_test]
+  async fn sleep_waits_for_duration() {
+    let start = Date::now();
+    sleep(Duration::from_millis(100)).await;
+    assert!(Date::now() - start >= 99.0); // Timers may round down by up to a millisecond
+  }
+
+  #[wasm_bindgen_test]
+  async fn dropped_sleep_never_fires() {
+    let timeouts = spy("setTimeout");
+    drop(sleep(Duration::from_millis(10)));
+    timeouts.restore();
+    sleep(Duration::from_millis(50)).await;
+    assert_eq!(timeouts.calls(), 0);
+  }
+
+  #[wasm_bindgen_test]
+  async fn interval_yields_every_period() {
+    let start = Date::now();
+    let mut ticks = interval(Duration::from_millis(20));
+    for _ in 0..3 {
+      assert_eq!(next(&mut ticks).await, Some(()));
+    }
+    assert!(Date::now() - start >= 59.0);
+  }
+
+  #[test]
+  fn clamps_long_durations() {
+    assert_eq!(super::millis(Duration::from_secs(u64::MAX)), i32::MAX);
+  }
+
+  #[wasm_bindgen_test]
+  async fn next_animation_frame_resolves_with_timestamp() {
+    let time = next_animation_frame().await;
+    assert!(time > 0.0);
+  }
+
+  #[wasm_bindgen_test]
+  async fn animation_frames_are_increasing() {
+    let mut frames = animation_frames();
+    let first = next(&mut frames).await.unwrap();
+    let second = next(&mut frames).await.unwrap();
+    assert!(second > first);
+  }
+
+  #[wasm_bindgen_test]
+  async fn dropped_animation_frames_are_cancelled() {
+    let mut frames = animation_frames();
+    next(&mut frames).await.unwrap();
+    let requests = spy("requestAnimationFrame");
+    drop(next_animation_frame());
+    // Requested again, but never polled, so dropping must cancel it before the released closure is called.
+    let mut cx = Context::from_waker(Waker::noop());
+    assert!(Pin::new(&mut frames).poll_next(&mut cx).is_pending());
+    drop(frames);
+    requests.restore();
+    next_animation_frame().await; // Runs after both cancelled frames would have
+    assert_eq!(requests.calls(), 0);
+  }
+
+  #[wasm_bindgen_test]
+  async fn idle_resolves_with_deadline() {
+    let deadline = idle().await;
+    assert!(deadline.time_remaining() >= 0.0);
+  }
+
+  #[wasm_bindgen_test]
+  async fn idle_with_timeout_resolves() {
+    let deadline = idle_with_timeout(Duration::from_millis(10)).await;
+    assert!(deadline.time_remaining() >= 0.0);
+  }
+
+  #[wasm_bindgen_test]
+  async fn dropped_idle_is_cancelled() {
+    let requests = spy(&idle_scheduler());
+    drop(idle());
+    requests.restore();
+    idle().await;
+    assert_eq!(requests.calls(), 0);
+  }
+
+  #[wasm_bindgen_test]
+  async fn yield_now_is_not_clamped() {
+    let start = Date::now();
+    for _ in 0..100 {
+      yield_now().await;
+    }
+    assert!(Date::now() - start < 250.0); // 100 clamped timeouts would take at least 400ms
+  }
+
+  #[wasm_bindgen_test]
+  async fn yield_now_runs_pending_macrotasks() {
+    let future = crate::Deferred::new();
+    web_sys::window()
+      .unwrap()
+      .set_timeout_with_callback_and_timeout_and_arguments_0(&future.as_functions().0, 0)
+      .unwrap();
+    let resolver = future.resolver();
+    while !resolver.is_settled() {
+      yield_now().await;
+    }
+    assert!(future.await.is_ok());
+  }
+}
@@ -0,0 +1,167 @@
+use crate::{Arity, Callback};
+use js_sys::{Array, Function};
+use std::future::Future;
+use std::marker::PhantomData;
+use std::pin::Pin;
+use std::task::Poll;
+use wasm_bindgen::{JsCast, JsValue};
+
+/// Converts a single callback argument into a rust value, failing with a `TypeError` if it has the wrong type.
+pub trait FromArg: Sized {
+  fn from_arg(arg: JsValue) -> Result<Self, JsValue>;
+}
+
+/// A struct built from the arguments a callback is called with, one field per argument, in order. Usually
+/// derived with `#[derive(FromCallbackArgs)]` from `js-function-promisify-macros`, where each field is converted
+/// with [`FromArg`], or with `JsCast` if marked `#[callback(cast)]`.
+pub trait FromCallbackArgs: Sized {
+  /// The number of arguments, as [`crate::Args`].
+  type Args: Arity;
+
+  /// Builds the struct from exactly as many arguments as `Args` takes.
+  fn from_args(args: Vec<JsValue>) -> Result<Self, JsValue>;
+}
+
+fn type_error<T>() -> JsValue {
+  js_sys::TypeError::new(&format!("expected a callback argument of type {}", std::any::type_name::<T>())).into()
+}
+
+impl FromArg for JsValue {
+  fn from_arg(arg: JsValue) -> Result<Self, JsValue> {
+    Ok(arg)
+  }
+}
+
+impl FromArg for String {
+  fn from_arg(arg: JsValue) -> Result<Self, JsValue> {
+    arg.as_string().ok_or_else(type_error::<Self>)
+  }
+}
+
+impl FromArg for bool {
+  fn from_arg(arg: JsValue) -> Result<Self, JsValue> {
+    arg.as_bool().ok_or_else(type_error::<Self>)
+  }
+}
+
+/// `null` and `undefined` convert to `None`, anything else as `T`.
+impl<T: FromArg> FromArg for Option<T> {
+  fn from_arg(arg: JsValue) -> Result<Self, JsValue> {
+    if arg.is_null() || arg.is_undefined() {
+      return Ok(None);
+    }
+    T::from_arg(arg).map(Some)
+  }
+}
+
+/// A utility macro for implementing `FromArg` for each float type, which converts like an `as` cast.
+macro_rules! float_impl {
+  ($($t:ty)*) => {
+    $(
+      impl FromArg for $t {
+        fn from_arg(arg: JsValue) -> Result<Self, JsValue> {
+          arg.as_f64().map(|n| n as $t).ok_or_else(type_error::<Self>)
+        }
+      }
+    )*
+  };
+}
+
+/// A utility macro for implementing `FromArg` for each integer type, which only accepts integral numbers within
+/// its range.
+macro_rules! integer_impl {
+  ($($t:ty)*) => {
+    $(
+      impl FromArg for $t {
+        fn from_arg(arg: JsValue) -> Result<Self, JsValue> {
+          match arg.as_f64() {
+            // The upper bound is exclusive, since `MAX` rounds up to the next power of two for the widest types.
+            Some(n) if n.fract() == 0.0 && n >= <$t>::MIN as f64 && n < <$t>::MAX as f64 + 1.0 => Ok(n as $t),
+            _ => Err(type_error::<Self>()),
+          }
+        }
+      }
+    )*
+  };
+}
+
+float_impl!(f64 f32);
+integer_impl!(i8 i16 i32 i64 isize u8 u16 u32 u64 usize);
+
+/// Converts an argument with `JsCast`, for fields marked `#[callback(cast)]`.
+#[doc(hidden)]
+pub fn cast_arg<T: JsCast>(arg: JsValue) -> Result<T, JsValue> {
+  arg.dyn_into().map_err(|_| type_error::<T>())
+}
+
+/// A callback which resolves with its arguments as `T`, created with [`Callback::typed`].
+pub struct Typed<T: FromCallbackArgs> {
+  future: Callback<<T::Args as Arity>::Signature>,
+  _marker: PhantomData<fn() -> T>,
+}
+
+impl<T: FromCallbackArgs> Typed<T> {
+  pub fn as_function(&self) -> Function {
+    self.future.as_function()
+  }
+}
+
+impl<T: FromCallbackArgs> Future for Typed<T> {
+  type Output = Result<T, JsValue>;
+
+  fn poll(self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> std::task::Poll<Self::Output> {
+    let this = self.get_mut();
+    match Pin::new(&mut this.future).poll(cx) {
+      Poll::Ready(Ok(args)) => Poll::Ready(T::from_args(Array::from(&args).to_vec())),
+      Poll::Ready(Err(err)) => Poll::Ready(Err(err)),
+      Poll::Pending => Poll::Pending,
+    }
+  }
+}
+
+impl Callback<dyn FnMut(JsValue)> {
+  /// Creates a callback taking as many arguments as `T` has fields, which resolves with them converted into
+  /// `T`, or rejects with a `TypeError` if one of them has the wrong type.
+  pub fn typed<T>() -> Typed<T>
+  where
+    T: FromCallbackArgs,
+    Callback<<T::Args as Arity>::Signature>: From<<T::Args as Arity>::Collect>,
+  {
+    Typed {
+      future: Callback::from(<T::Args as Arity>::COLLECT),
+      _marker: PhantomData,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::FromArg;
+  use wasm_bindgen::JsValue;
+  use wasm_bindgen_test::*;
+
+  wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+  #[wasm_bindgen_test]
+  fn integers_accept_integral_values_in_range() {
+    assert_eq!(u8::from_arg(255.into()).unwrap(), 255);
+    assert_eq!(i32::from_arg((-3).into()).unwrap(), -3);
+    assert_eq!(u32::from_arg(3.0.into()).unwrap(), 3);
+  }
+
+  #[wasm_bindgen_test]
+  fn integers_reject_lossy_values() {
+    assert!(u32::from_arg(3.7.into()).is_err());
+    assert!(u32::from_arg((-1).into()).is_err());
+    assert!(u8::from_arg(256.into()).is_err());
+    assert!(i64::from_arg(9223372036854775808.0.into()).is_err()); // 2^63, just past `i64::MAX`
+    assert!(u32::from_arg(JsValue::from(f64::NAN)).is_err());
+    assert!(u32::from_arg(JsValue::from(f64::INFINITY)).is_err());
+  }
+
+  #[wasm_bindgen_test]
+  fn floats_convert_like_a_cast() {
+    assert_eq!(f64::from_arg(3.7.into()).unwrap(), 3.7);
+    assert!(f64::from_arg(JsValue::from(f64::NAN)).unwrap().is_nan());
+  }
+}
@@ -0,0 +1,51 @@
+use js_function_promisify::Callback;
+use js_function_promisify_macros::FromCallbackArgs;
+use js_sys::{Function, Uint8Array};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+#[wasm_bindgen(inline_js = "export function read(bytes, cb) { setTimeout(() => cb(null, bytes, new Uint8Array(bytes))); };
+  export function measure(cb) { setTimeout(() => cb(640, 480)); };")]
+extern "C" {
+  fn read(bytes: &JsValue, cb: &Function);
+  fn measure(cb: &Function);
+}
+
+#[derive(FromCallbackArgs)]
+struct Read {
+  err: Option<JsValue>,
+  bytes_read: u32,
+  #[callback(cast)]
+  buffer: Uint8Array,
+}
+
+#[derive(FromCallbackArgs)]
+struct Size(f64, f64);
+
+#[wasm_bindgen_test]
+async fn named_fields_by_position() {
+  let future = Callback::typed::<Read>();
+  read(&4.into(), &future.as_function());
+  let result = future.await.unwrap();
+  assert!(result.err.is_none());
+  assert_eq!(result.bytes_read, 4);
+  assert_eq!(result.buffer.length(), 4);
+}
+
+#[wasm_bindgen_test]
+async fn tuple_fields_by_position() {
+  let future = Callback::typed::<Size>();
+  measure(&future.as_function());
+  let Size(width, height) = future.await.unwrap();
+  assert_eq!((width, height), (640.0, 480.0));
+}
+
+#[wasm_bindgen_test]
+async fn wrong_type_rejects() {
+  let future = Callback::typed::<Read>();
+  read(&"four".into(), &future.as_function());
+  let err = future.await.err().unwrap();
+  assert!(err.is_instance_of::<js_sys::TypeError>());
+}
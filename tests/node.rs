@@ -0,0 +1,128 @@
+#![cfg(feature = "node")]
+use js_function_promisify::node::{self, EventEmitter};
+use js_sys::{Array, Object, Reflect};
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::*;
+
+// Unlike the other tests, these run in node: `wasm-pack test --node --features node`
+
+#[wasm_bindgen(inline_js = "export function process() { return globalThis.process; };
+  export function emit(emitter, event, value) { emitter.emit(event, value); };
+  export function listener_count(emitter, event) { return emitter.listenerCount(event); };
+  export function custom_promisified() { const f = (cb) => cb('wrong'); f[Symbol.for('nodejs.util.promisify.custom')] = (x) => Promise.resolve('custom ' + x); return f; };
+  export function client() { class Client { get(key, cb) { setTimeout(() => cb(null, 'value of ' + key)); } }; return new Client(); };
+  export function fs() { return { read(path, cb) { read_file(path, cb); }, sep: '/' }; };
+  export function read_file(path, cb) { setTimeout(() => path === 'missing' ? cb('ENOENT') : cb(null, 'contents of ' + path)); };")]
+extern "C" {
+  fn process() -> EventEmitter;
+  fn emit(emitter: &EventEmitter, event: &str, value: &JsValue);
+  fn listener_count(emitter: &EventEmitter, event: &str) -> u32;
+  fn custom_promisified() -> js_sys::Function;
+  fn client() -> Object;
+  fn fs() -> Object;
+  fn read_file(path: &str, cb: &js_sys::Function);
+}
+
+#[wasm_bindgen_test]
+async fn once_resolves_with_event() {
+  let emitter = process();
+  let future = node::once(&emitter, "promisify-resolve");
+  emit(&emitter, "promisify-resolve", &"payload".into());
+  let result = future.await;
+  assert_eq!(result.unwrap(), "payload");
+  assert_eq!(listener_count(&emitter, "promisify-resolve"), 0);
+}
+
+#[wasm_bindgen_test]
+async fn once_rejects_with_error() {
+  let emitter = process();
+  let error_listeners = listener_count(&emitter, "error");
+  let future = node::once(&emitter, "promisify-reject");
+  emit(&emitter, "error", &"failure".into());
+  let result = future.await;
+  assert_eq!(result.unwrap_err(), "failure");
+  assert_eq!(listener_count(&emitter, "promisify-reject"), 0);
+  assert_eq!(listener_count(&emitter, "error"), error_listeners);
+}
+
+#[wasm_bindgen_test]
+fn once_detaches_when_dropped() {
+  let emitter = process();
+  drop(node::once(&emitter, "promisify-drop"));
+  assert_eq!(listener_count(&emitter, "promisify-drop"), 0);
+}
+
+#[wasm_bindgen_test]
+async fn call_appends_node_callback() {
+  let func: js_sys::Function = Closure::once_into_js(|path: String, cb: js_sys::Function| read_file(&path, &cb)).into();
+  let result = node::call(&JsValue::NULL, &func, &Array::of1(&"a.txt".into())).await;
+  assert_eq!(result.unwrap(), "contents of a.txt");
+}
+
+#[wasm_bindgen_test]
+async fn call_method_rejects_with_err() {
+  let fs = Object::new();
+  let func: JsValue = Closure::once_into_js(|path: String, cb: js_sys::Function| read_file(&path, &cb));
+  Reflect::set(&fs, &"readFile".into(), &func).unwrap();
+  let result = node::call_method(&fs, "readFile", &Array::of1(&"missing".into())).await;
+  assert_eq!(result.unwrap_err(), "ENOENT");
+}
+
+#[wasm_bindgen_test]
+async fn call_method_requires_function() {
+  let result = node::call_method(&Object::new(), "readFile", &Array::new()).await;
+  assert!(result.is_err()); // Assert is `Err`
+}
+
+#[wasm_bindgen_test]
+async fn call_prefers_custom_promisified() {
+  let result = node::call(&JsValue::NULL, &custom_promisified(), &Array::of1(&"value".into())).await;
+  assert_eq!(result.unwrap(), "custom value");
+}
+
+#[wasm_bindgen_test]
+async fn call_method_uses_custom_set_timeout() {
+  let result = node::call_method(&js_sys::global(), "setTimeout", &Array::of2(&10.into(), &"done".into())).await;
+  assert_eq!(result.unwrap(), "done");
+}
+
+#[wasm_bindgen_test]
+async fn promisify_all_calls_and_caches() {
+  let fs = node::promisify_all(&fs());
+  let result = fs.call_async("read", &Array::of1(&"a.txt".into())).await;
+  assert_eq!(result.unwrap(), "contents of a.txt");
+  assert_eq!(JsValue::from(fs.method("read").unwrap()), JsValue::from(fs.method("read").unwrap()));
+  assert!(fs.call_async("sep", &Array::new()).await.is_err()); // Assert is `Err`
+}
+
+#[wasm_bindgen_test]
+async fn promisify_all_proxy() {
+  let proxy = node::promisify_all(&fs()).as_proxy();
+  let read: js_sys::Function = Reflect::get(&proxy, &"read".into()).unwrap().into();
+  let promise: js_sys::Promise = read.call1(&JsValue::NULL, &"missing".into()).unwrap().into();
+  assert_eq!(wasm_bindgen_futures::JsFuture::from(promise).await.unwrap_err(), "ENOENT");
+  assert_eq!(Reflect::get(&proxy, &"sep".into()).unwrap(), "/");
+}
+
+#[wasm_bindgen_test]
+fn promisify_all_proxy_passes_inherited_methods_through() {
+  let object = fs();
+  let proxy = node::promisify_all(&object).as_proxy();
+  for name in ["toString", "hasOwnProperty"] {
+    assert_eq!(Reflect::get(&proxy, &name.into()).unwrap(), Reflect::get(&object, &name.into()).unwrap());
+  }
+  assert_eq!(proxy.unchecked_ref::<Object>().to_string(), "[object Object]");
+  let has_own: js_sys::Function = Reflect::get(&proxy, &"hasOwnProperty".into()).unwrap().into();
+  assert_eq!(has_own.call1(&proxy, &"read".into()).unwrap(), true); // Returns directly, rather than a promise
+}
+
+#[wasm_bindgen_test]
+async fn promisify_all_proxy_wraps_class_methods() {
+  let object = client();
+  let proxy = node::promisify_all(&object).as_proxy();
+  let get: js_sys::Function = Reflect::get(&proxy, &"get".into()).unwrap().into();
+  let promise: js_sys::Promise = get.call1(&JsValue::NULL, &"a".into()).unwrap().into();
+  assert_eq!(wasm_bindgen_futures::JsFuture::from(promise).await.unwrap(), "value of a");
+  let constructor = Reflect::get(&object, &"constructor".into()).unwrap();
+  assert_eq!(Reflect::get(&proxy, &"constructor".into()).unwrap(), constructor);
+}
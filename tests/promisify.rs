@@ -0,0 +1,101 @@
+use js_function_promisify_macros::promisify;
+use js_sys::Function;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen_test::*;
+
+wasm_bindgen_test::wasm_bindgen_test_configure!(run_in_browser);
+
+#[promisify]
+#[wasm_bindgen(inline_js = "export function after(ms, value, cb) { setTimeout(() => cb(value), ms); };
+  export function read_file(path, cb) { setTimeout(() => path === 'missing' ? cb('ENOENT') : cb(null, 'contents of ' + path)); };
+  export function load(ok, err, url) { setTimeout(() => url ? ok(url) : err('no url')); };
+  export function echo(future, cb) { setTimeout(() => cb(future)); };
+  export function throws(cb) { throw new Error('thrown'); };
+  export class Loader { fetch(url, cb) { setTimeout(() => cb(url)); } };")]
+extern "C" {
+  #[promisify(callback = cb)]
+  fn after(ms: u32, value: &JsValue, cb: &Function);
+
+  #[promisify(node = cb)]
+  fn read_file(path: &str, cb: &Function);
+
+  #[promisify(resolve = ok, reject = err, rename = load_url)]
+  fn load(ok: &Function, err: &Function, url: &str);
+
+  #[promisify(callback = cb)]
+  fn echo(future: &JsValue, cb: &Function);
+
+  #[wasm_bindgen(catch)]
+  #[promisify(callback = cb)]
+  fn throws(cb: &Function) -> Result<(), JsValue>;
+
+  type Loader;
+
+  #[wasm_bindgen(constructor)]
+  fn new() -> Loader;
+
+  #[wasm_bindgen(method)]
+  #[promisify(callback = cb)]
+  fn fetch(this: &Loader, url: &str, cb: &Function);
+}
+
+#[wasm_bindgen_test]
+async fn callback_resolves() {
+  assert_eq!(after_async(10, &"done".into()).await.unwrap(), "done");
+}
+
+#[wasm_bindgen_test]
+async fn node_callback_resolves_and_rejects() {
+  assert_eq!(read_file_async("a.txt").await.unwrap(), "contents of a.txt");
+  assert_eq!(read_file_async("missing").await.unwrap_err(), "ENOENT");
+}
+
+#[wasm_bindgen_test]
+async fn pair_rejects_when_renamed() {
+  assert_eq!(load_url("").await.unwrap_err(), "no url");
+}
+
+#[wasm_bindgen_test]
+async fn argument_named_future_is_not_shadowed() {
+  assert_eq!(echo_async(&"value".into()).await.unwrap(), "value");
+}
+
+#[wasm_bindgen_test]
+async fn catch_returns_exception() {
+  assert!(throws_async().await.is_err()); // Assert is `Err`
+}
+
+#[wasm_bindgen_test]
+async fn method_is_called_on_this() {
+  assert_eq!(fetch_async(&Loader::new(), "a.txt").await.unwrap(), "a.txt");
+}
+
+#[wasm_bindgen_test]
+async fn bang_replaces_placeholder() {
+  let value = JsValue::from("done");
+  assert_eq!(js_function_promisify::promisify!(after(10, &value, _)).await.unwrap(), "done");
+}
+
+#[wasm_bindgen_test]
+async fn bang_node_convention() {
+  assert_eq!(js_function_promisify::promisify!(node; read_file("a.txt", _)).await.unwrap(), "contents of a.txt");
+  assert_eq!(js_function_promisify::promisify!(node; read_file("missing", _)).await.unwrap_err(), "ENOENT");
+}
+
+#[wasm_bindgen_test]
+async fn bang_replaces_pair_placeholders() {
+  assert_eq!(js_function_promisify::promisify!(load(_ok, _err, "a.png")).await.unwrap(), "a.png");
+  assert_eq!(js_function_promisify::promisify!(load(_ok, _err, "")).await.unwrap_err(), "no url");
+}
+
+#[wasm_bindgen_test]
+async fn bang_calls_methods() {
+  let loader = Loader::new();
+  let url = js_function_promisify::promisify!(loader.fetch(&format!("{}.txt", "a"), _)).await;
+  assert_eq!(url.unwrap(), "a.txt");
+}
+
+#[wasm_bindgen_test]
+async fn bang_catch_returns_exception() {
+  assert!(js_function_promisify::promisify!(throws(_)).await.is_err()); // Assert is `Err`
+}
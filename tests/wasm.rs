@@ -11,6 +11,6 @@ async fn readme_example() {
     .set_timeout_with_callback_and_timeout_and_arguments_0(future.as_function().as_ref(), 500)
     .unwrap();
   let result = future.await; // result: Result<JsValue, JsValue>
-  assert_eq!(result.is_ok(), true); // Assert `Ok`
+  assert!(result.is_ok()); // Assert `Ok`
   assert_eq!(result.unwrap().as_string().unwrap(), "Hello future!"); // Assert the result exactly equals the string
 }